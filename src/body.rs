@@ -1,11 +1,12 @@
 use std::fmt;
 use std::io::Write;
 
-use http::{HeaderName, HeaderValue, Method};
+use http::{HeaderMap, HeaderName, HeaderValue, Method};
 
-use crate::chunk::Dechunker;
+use crate::chunk::{ChunkExtension, Dechunker};
+use crate::redact::RedactedHeaders;
 use crate::util::{compare_lowercase_ascii, log_data, Writer};
-use crate::Error;
+use crate::{Error, Limits, ParsePolicy};
 
 #[derive(Debug, Clone, Copy, Default)]
 pub(crate) struct BodyWriter {
@@ -18,6 +19,7 @@ enum SenderMode {
     None,
     Sized(u64),
     Chunked,
+    CloseDelimited,
 }
 
 impl Default for SenderMode {
@@ -54,8 +56,18 @@ impl BodyWriter {
         }
     }
 
+    pub fn new_close_delimited() -> Self {
+        BodyWriter {
+            mode: SenderMode::CloseDelimited,
+            ended: false,
+        }
+    }
+
     pub fn has_body(&self) -> bool {
-        matches!(self.mode, SenderMode::Sized(_) | SenderMode::Chunked)
+        matches!(
+            self.mode,
+            SenderMode::Sized(_) | SenderMode::Chunked | SenderMode::CloseDelimited
+        )
     }
 
     pub fn is_chunked(&self) -> bool {
@@ -100,7 +112,43 @@ impl BodyWriter {
 
                 input_used
             }
+            SenderMode::CloseDelimited => {
+                if input.is_empty() {
+                    self.ended = true;
+                    return 0;
+                }
+
+                let to_write = w.available().min(input.len());
+
+                let success = w.try_write(|w| w.write_all(&input[..to_write]));
+                assert!(success);
+
+                to_write
+            }
+        }
+    }
+
+    /// End a chunked body with trailer fields after the last chunk.
+    ///
+    /// The entire ending is written in one go, or nothing at all if the output is too small.
+    pub fn write_trailers(&mut self, trailers: &HeaderMap, w: &mut Writer) -> bool {
+        assert!(self.is_chunked());
+
+        let success = w.try_write(|w| {
+            w.write_all(b"0\r\n")?;
+            for (name, value) in trailers {
+                write!(w, "{}: ", name)?;
+                w.write_all(value.as_bytes())?;
+                write!(w, "\r\n")?;
+            }
+            write!(w, "\r\n")
+        });
+
+        if success {
+            self.ended = true;
         }
+
+        success
     }
 
     fn finish(&self, w: &mut Writer) -> bool {
@@ -113,8 +161,8 @@ impl BodyWriter {
         true
     }
 
-    pub(crate) fn body_header(&self) -> (HeaderName, HeaderValue) {
-        match self.mode {
+    pub(crate) fn body_header(&self) -> Result<(HeaderName, HeaderValue), Error> {
+        let header = match self.mode {
             SenderMode::None => unreachable!(),
             SenderMode::Sized(size) => (
                 HeaderName::from_static("content-length"),
@@ -125,7 +173,12 @@ impl BodyWriter {
                 HeaderName::from_static("transfer-encoding"),
                 HeaderValue::from_static("chunked"),
             ),
-        }
+            // The end of the body is signaled by closing the connection, and there
+            // is no header to describe that.
+            SenderMode::CloseDelimited => return Err(Error::BodyIsCloseDelimited),
+        };
+
+        Ok(header)
     }
 
     pub(crate) fn is_ended(&self) -> bool {
@@ -150,10 +203,38 @@ impl BodyWriter {
                 }
             }
             SenderMode::Chunked => unreachable!(),
+            SenderMode::CloseDelimited => {}
         }
     }
 }
 
+/// Check that all trailer fields are announced by a `trailer` header.
+///
+/// The `announced` values are comma separated lists of field names.
+pub(crate) fn check_announced_trailers<'a>(
+    announced: impl Iterator<Item = &'a HeaderValue>,
+    trailers: &HeaderMap,
+) -> Result<(), Error> {
+    let announced: Vec<&str> = announced
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(|v| v.trim())
+        .collect();
+
+    for name in trailers.keys() {
+        // HeaderName is always lowercase.
+        let is_announced = announced
+            .iter()
+            .any(|a| compare_lowercase_ascii(a, name.as_str()));
+
+        if !is_announced {
+            return Err(Error::TrailerNotAnnounced(name.to_string()));
+        }
+    }
+
+    Ok(())
+}
+
 #[allow(unused)]
 pub(crate) fn calculate_chunk_overhead(output_len: usize) -> usize {
     // The + 1 and floor() is to make even powers of 16 right.
@@ -213,7 +294,7 @@ fn write_chunk(input: &[u8], input_used: &mut usize, w: &mut Writer, max_chunk:
     success && input.len() > to_write
 }
 
-#[derive(Clone, Copy, PartialEq, Eq)]
+#[derive(Clone, PartialEq, Eq)]
 pub(crate) enum BodyReader {
     /// No body is expected either due to the status or method.
     NoBody,
@@ -223,7 +304,8 @@ pub(crate) enum BodyReader {
     /// Chunked transfer encoding
     Chunked(Dechunker),
     /// Expect remote to close at end of body.
-    CloseDelimited,
+    /// The value is whether the remote has closed.
+    CloseDelimited(bool),
 }
 
 /// Kind of body
@@ -248,45 +330,45 @@ impl BodyReader {
             // not the total length, but the the remaining.
             BodyReader::LengthDelimited(v) => BodyMode::LengthDelimited(*v),
             BodyReader::Chunked(_) => BodyMode::Chunked,
-            BodyReader::CloseDelimited => BodyMode::CloseDelimited,
+            BodyReader::CloseDelimited(_) => BodyMode::CloseDelimited,
         }
     }
 
-    // pub fn for_request<'a>(
-    //     http10: bool,
-    //     method: &Method,
-    //     header_lookup: &'a dyn Fn(&str) -> Option<&'a str>,
-    // ) -> Result<Self, Error> {
-    //     let has_no_body = !method.need_request_body();
-
-    //     if has_no_body {
-    //         return Ok(Self::LengthDelimited(0));
-    //     }
-
-    //     let ret = match Self::header_defined(http10, header_lookup)? {
-    //         // Request bodies cannot be close delimited (even under http10).
-    //         Self::CloseDelimited => Self::LengthDelimited(0),
-    //         r @ _ => r,
-    //     };
+    pub fn for_request(
+        http10: bool,
+        headers: &HeaderMap,
+        policy: &ParsePolicy,
+        limits: &Limits,
+    ) -> Result<Self, Error> {
+        // https://www.rfc-editor.org/rfc/rfc9112#section-6.3
+        // If this is a request message and none of the above are true, then the
+        // message body length is zero (no message body is present).
+        //
+        // This is regardless of method, i.e. a GET is allowed to send a body, and a
+        // POST without content-length or transfer-encoding has no body.
+        let ret = match Self::header_defined(http10, true, headers, policy, limits)? {
+            // Request bodies cannot be close delimited (even under http10).
+            Self::CloseDelimited(_) => Self::NoBody,
+            r => r,
+        };
 
-    //     Ok(ret)
-    // }
+        Ok(ret)
+    }
 
-    pub fn for_response<'a>(
+    pub fn for_response(
         http10: bool,
         method: &Method,
         status_code: u16,
-        header_lookup: &'a dyn Fn(&str) -> Option<&'a str>,
+        headers: &HeaderMap,
+        policy: &ParsePolicy,
+        limits: &Limits,
     ) -> Result<Self, Error> {
         let is_success = (200..=299).contains(&status_code);
         let is_informational = (100..=199).contains(&status_code);
         let is_redirect = (300..=399).contains(&status_code) && status_code != 304;
 
-        let header_defined = Self::header_defined(http10, header_lookup)?;
-
-        // Implicitly we know that CloseDelimited means no header indicated that
-        // there was a body.
-        let has_body_header = header_defined != Self::CloseDelimited;
+        let has_body_header =
+            headers.contains_key("content-length") || headers.contains_key("transfer-encoding");
 
         let has_no_body =
             // https://datatracker.ietf.org/doc/html/rfc2616#section-4.3
@@ -311,48 +393,88 @@ impl BodyReader {
 
         // https://datatracker.ietf.org/doc/html/rfc2616#section-4.3
         // All other responses do include a message-body, although it MAY be of zero length.
-        Ok(header_defined)
+        Self::header_defined(http10, false, headers, policy, limits)
     }
 
-    fn header_defined<'a>(
+    /// Message body length from the headers (RFC 9112 6.3).
+    ///
+    /// All instances of the headers are considered. Ambiguous framing is an error, since
+    /// a sender and recipient disagreeing on the body length is the basis for request
+    /// smuggling and response splitting.
+    fn header_defined(
         http10: bool,
-        header_lookup: &'a dyn Fn(&str) -> Option<&'a str>,
+        is_request: bool,
+        headers: &HeaderMap,
+        policy: &ParsePolicy,
+        limits: &Limits,
     ) -> Result<Self, Error> {
-        let mut content_length: Option<u64> = None;
-        let mut chunked = false;
+        let content_length = content_length(headers)?;
 
-        // for head in headers {
-        if let Some(value) = header_lookup("content-length") {
-            let v = value
-                .parse::<u64>()
-                .map_err(|_| Error::BadContentLengthHeader)?;
-            if content_length.is_some() {
-                return Err(Error::TooManyContentLengthHeaders);
+        if !headers.contains_key("transfer-encoding") {
+            if let Some(len) = content_length {
+                return Ok(Self::LengthDelimited(len));
             }
-            content_length = Some(v);
+
+            return Ok(Self::CloseDelimited(false));
+        }
+
+        // https://www.rfc-editor.org/rfc/rfc9112#section-6.3
+        // If a message is received with both a Transfer-Encoding and a Content-Length
+        // header field, the Transfer-Encoding overrides the Content-Length. Such a
+        // message might indicate an attempt to perform request smuggling or response
+        // splitting and ought to be handled as an error.
+        //
+        // A server may reject such a request, which we always do. For responses the
+        // Transfer-Encoding wins, unless the policy says otherwise.
+        let allowed = !is_request && policy.allow_content_length_with_transfer_encoding;
+        if content_length.is_some() && !allowed {
+            return Err(Error::ContentLengthAndTransferEncoding);
+        }
+
+        let mut codings = Vec::new();
+
+        for value in headers.get_all("transfer-encoding") {
+            let value = value
+                .to_str()
+                .map_err(|_| Error::BadTransferEncodingHeader)?;
+
+            codings.extend(value.split(',').map(|v| v.trim()).filter(|v| !v.is_empty()));
         }
 
-        if let Some(value) = header_lookup("transfer-encoding") {
-            // Header can repeat, stop looking if we found "chunked"
-            chunked = value
-                .split(',')
-                .map(|v| v.trim())
-                .any(|v| compare_lowercase_ascii(v, "chunked"));
+        let chunked_count = codings
+            .iter()
+            .filter(|v| compare_lowercase_ascii(v, "chunked"))
+            .count();
+        let chunked_last = codings
+            .last()
+            .map(|v| compare_lowercase_ascii(v, "chunked"))
+            .unwrap_or(false);
+
+        // https://www.rfc-editor.org/rfc/rfc9112#section-6.1
+        // A sender MUST NOT apply the chunked transfer coding more than once to a message body.
+        if chunked_count > 1 {
+            return Err(Error::BadTransferEncodingHeader);
         }
 
-        if chunked && !http10 {
-            // https://datatracker.ietf.org/doc/html/rfc2616#section-4.4
-            // Messages MUST NOT include both a Content-Length header field and a
-            // non-identity transfer-coding. If the message does include a non-
-            // identity transfer-coding, the Content-Length MUST be ignored.
-            return Ok(Self::Chunked(Dechunker::new()));
+        // https://www.rfc-editor.org/rfc/rfc9112#section-6.1
+        // A server or client that receives an HTTP/1.0 message containing a Transfer-Encoding
+        // header field MUST treat the message as if the framing is faulty, even if a
+        // Content-Length is present, and close the connection after processing the message.
+        if chunked_last && !http10 {
+            return Ok(Self::Chunked(Dechunker::new(policy, limits)));
         }
 
-        if let Some(len) = content_length {
-            return Ok(Self::LengthDelimited(len));
+        // https://www.rfc-editor.org/rfc/rfc9112#section-6.3
+        // If a Transfer-Encoding header field is present in a response and the chunked transfer
+        // coding is not the final encoding, the message body length is determined by reading
+        // the connection until it is closed by the server. If a Transfer-Encoding header field
+        // is present in a request and the chunked transfer coding is not the final encoding,
+        // the message body length cannot be determined reliably.
+        if is_request {
+            return Err(Error::BadTransferEncodingHeader);
         }
 
-        Ok(Self::CloseDelimited)
+        Ok(Self::CloseDelimited(false))
     }
 
     pub fn read(
@@ -360,16 +482,17 @@ impl BodyReader {
         src: &[u8],
         dst: &mut [u8],
         stop_on_chunk_boundary: bool,
+        redacted: &RedactedHeaders,
     ) -> Result<(usize, usize), Error> {
         // unwrap is ok because we can't be in state RECV_BODY without setting it.
         let part = match self {
             BodyReader::LengthDelimited(_) => self.read_limit(src, dst),
             BodyReader::Chunked(_) => self.read_chunked(src, dst, stop_on_chunk_boundary),
-            BodyReader::CloseDelimited => self.read_unlimit(src, dst),
+            BodyReader::CloseDelimited(_) => self.read_unlimit(src, dst),
             BodyReader::NoBody => return Ok((0, 0)),
         }?;
 
-        log_data(&src[..part.0]);
+        log_data(&src[..part.0], redacted);
 
         Ok(part)
     }
@@ -439,27 +562,88 @@ impl BodyReader {
             BodyReader::NoBody => true,
             BodyReader::LengthDelimited(v) => *v == 0,
             BodyReader::Chunked(v) => v.is_ended(),
-            BodyReader::CloseDelimited => false,
+            BodyReader::CloseDelimited(closed) => *closed,
         }
     }
 
+    /// Handle that the remote closed the connection.
+    ///
+    /// This ends a close delimited body, but for any other body that isn't ended,
+    /// the body is truncated.
+    pub(crate) fn signal_eof(&mut self) -> Result<(), Error> {
+        if let BodyReader::CloseDelimited(closed) = self {
+            *closed = true;
+        }
+
+        if !self.is_ended() {
+            return Err(Error::IncompleteBody);
+        }
+
+        Ok(())
+    }
+
     pub(crate) fn is_on_chunk_boundary(&self) -> bool {
         match self {
             BodyReader::NoBody => false,
             BodyReader::LengthDelimited(_) => false,
             BodyReader::Chunked(v) => v.is_on_chunk_boundary(),
-            BodyReader::CloseDelimited => false,
+            BodyReader::CloseDelimited(_) => false,
+        }
+    }
+
+    pub(crate) fn chunk_extensions(&self) -> &[ChunkExtension] {
+        match self {
+            BodyReader::Chunked(v) => v.extensions(),
+            _ => &[],
+        }
+    }
+
+    pub(crate) fn trailers(&self) -> Option<&HeaderMap> {
+        match self {
+            BodyReader::Chunked(v) if v.is_ended() => Some(v.trailers()),
+            _ => None,
         }
     }
 }
 
+/// Parse all content-length headers.
+///
+/// A list of identical values, such as `content-length: 5, 5`, is accepted as one
+/// value. Differing values are an error (RFC 9112 6.3).
+fn content_length(headers: &HeaderMap) -> Result<Option<u64>, Error> {
+    let mut content_length: Option<u64> = None;
+
+    for value in headers.get_all("content-length") {
+        let value = value.to_str().map_err(|_| Error::BadContentLengthHeader)?;
+
+        for v in value.split(',').map(|v| v.trim()) {
+            // Content-Length = 1*DIGIT. This rules out "+5", which parse() accepts.
+            if v.is_empty() || !v.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(Error::BadContentLengthHeader);
+            }
+
+            let n = v
+                .parse::<u64>()
+                .map_err(|_| Error::BadContentLengthHeader)?;
+
+            if content_length.map(|c| c != n).unwrap_or(false) {
+                return Err(Error::TooManyContentLengthHeaders);
+            }
+
+            content_length = Some(n);
+        }
+    }
+
+    Ok(content_length)
+}
+
 impl fmt::Debug for BodyReader {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Self::NoBody => write!(f, "NoBody"),
             Self::LengthDelimited(arg0) => f.debug_tuple("LengthDelimited").field(arg0).finish(),
             Self::Chunked(_) => write!(f, "Chunked"),
-            Self::CloseDelimited => write!(f, "CloseDelimited"),
+            Self::CloseDelimited(_) => write!(f, "CloseDelimited"),
         }
     }
 }
@@ -486,4 +670,114 @@ mod test {
         assert_eq!(calculate_max_input(10258), 10242);
         assert_eq!(calculate_max_input(10259), 10243);
     }
+
+    fn headers(list: &[(&str, &str)]) -> HeaderMap {
+        let mut map = HeaderMap::new();
+        for (name, value) in list {
+            map.append(
+                HeaderName::from_bytes(name.as_bytes()).unwrap(),
+                HeaderValue::from_str(value).unwrap(),
+            );
+        }
+        map
+    }
+
+    fn response(list: &[(&str, &str)]) -> Result<BodyReader, Error> {
+        BodyReader::for_response(
+            false,
+            &Method::GET,
+            200,
+            &headers(list),
+            &ParsePolicy::default(),
+            &Limits::default(),
+        )
+    }
+
+    fn request(list: &[(&str, &str)]) -> Result<BodyReader, Error> {
+        BodyReader::for_request(
+            false,
+            &headers(list),
+            &ParsePolicy::default(),
+            &Limits::default(),
+        )
+    }
+
+    #[test]
+    fn test_repeated_content_length() {
+        let r = response(&[("content-length", "5"), ("content-length", "5")]).unwrap();
+        assert!(matches!(r, BodyReader::LengthDelimited(5)));
+
+        let r = response(&[("content-length", "5, 5")]).unwrap();
+        assert!(matches!(r, BodyReader::LengthDelimited(5)));
+
+        let r = response(&[("content-length", "5"), ("content-length", "6")]);
+        assert_eq!(r.unwrap_err(), Error::TooManyContentLengthHeaders);
+
+        let r = request(&[("content-length", "5, 6")]);
+        assert_eq!(r.unwrap_err(), Error::TooManyContentLengthHeaders);
+
+        let r = response(&[("content-length", "+5")]);
+        assert_eq!(r.unwrap_err(), Error::BadContentLengthHeader);
+    }
+
+    #[test]
+    fn test_repeated_transfer_encoding() {
+        let r = response(&[
+            ("transfer-encoding", "gzip"),
+            ("transfer-encoding", "chunked"),
+        ]);
+        assert!(matches!(r.unwrap(), BodyReader::Chunked(_)));
+
+        let r = response(&[("transfer-encoding", "gzip, chunked")]);
+        assert!(matches!(r.unwrap(), BodyReader::Chunked(_)));
+
+        // Chunked not last is close delimited for responses, and an error for requests.
+        let r = response(&[
+            ("transfer-encoding", "chunked"),
+            ("transfer-encoding", "gzip"),
+        ]);
+        assert!(matches!(r.unwrap(), BodyReader::CloseDelimited(false)));
+
+        let r = request(&[("transfer-encoding", "chunked, gzip")]);
+        assert_eq!(r.unwrap_err(), Error::BadTransferEncodingHeader);
+
+        let r = response(&[("transfer-encoding", "gzip")]);
+        assert!(matches!(r.unwrap(), BodyReader::CloseDelimited(false)));
+
+        let r = request(&[("transfer-encoding", "gzip")]);
+        assert_eq!(r.unwrap_err(), Error::BadTransferEncodingHeader);
+
+        let r = request(&[("transfer-encoding", "chunked, chunked")]);
+        assert_eq!(r.unwrap_err(), Error::BadTransferEncodingHeader);
+    }
+
+    #[test]
+    fn test_content_length_and_transfer_encoding() {
+        let r = response(&[("content-length", "5"), ("transfer-encoding", "chunked")]);
+        assert!(matches!(r.unwrap(), BodyReader::Chunked(_)));
+
+        let r = BodyReader::for_response(
+            false,
+            &Method::GET,
+            200,
+            &headers(&[("content-length", "5"), ("transfer-encoding", "chunked")]),
+            &ParsePolicy::strict(),
+            &Limits::default(),
+        );
+        assert_eq!(r.unwrap_err(), Error::ContentLengthAndTransferEncoding);
+
+        let r = request(&[("transfer-encoding", "chunked"), ("content-length", "5")]);
+        assert_eq!(r.unwrap_err(), Error::ContentLengthAndTransferEncoding);
+
+        // Framing headers are ignored for responses without body.
+        let r = BodyReader::for_response(
+            false,
+            &Method::HEAD,
+            200,
+            &headers(&[("content-length", "5"), ("transfer-encoding", "chunked")]),
+            &ParsePolicy::default(),
+            &Limits::default(),
+        );
+        assert!(matches!(r.unwrap(), BodyReader::NoBody));
+    }
 }
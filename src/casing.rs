@@ -0,0 +1,79 @@
+use std::io::{self, Write};
+
+use http::HeaderName;
+
+/// How header names are written on the wire.
+///
+/// Header names are case insensitive, and [`HeaderName`] is always lowercase. Some
+/// devices nevertheless require a specific casing, such as `Host:` rather than `host:`.
+///
+/// The casing is set on a flow in the `Prepare` state via
+/// [`Flow::set_header_casing()`](crate::client::flow::Flow::set_header_casing), and
+/// applies to all request headers, including those added by the flow, such as `host`,
+/// `content-length` and `transfer-encoding`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum HeaderCasing {
+    /// As is, i.e. `content-length`.
+    #[default]
+    Lowercase,
+
+    /// Uppercase first letter and each letter following a `-`, i.e. `Content-Length`.
+    TitleCase,
+
+    /// The casing of these names, i.e. `vec!["X-API-Key".into()]`.
+    ///
+    /// The names are matched case insensitively. Headers not in the list are lowercase.
+    Preserve(Vec<String>),
+}
+
+impl HeaderCasing {
+    pub(crate) fn write_name(&self, w: &mut impl Write, name: &HeaderName) -> io::Result<()> {
+        let name = name.as_str();
+
+        match self {
+            HeaderCasing::Lowercase => w.write_all(name.as_bytes()),
+            HeaderCasing::TitleCase => {
+                let mut upper = true;
+                for c in name.bytes() {
+                    let c = if upper { c.to_ascii_uppercase() } else { c };
+                    upper = c == b'-';
+                    w.write_all(&[c])?;
+                }
+                Ok(())
+            }
+            HeaderCasing::Preserve(names) => {
+                let preserved = names.iter().find(|n| n.eq_ignore_ascii_case(name));
+                let name = preserved.map(|n| n.as_str()).unwrap_or(name);
+                w.write_all(name.as_bytes())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn write(casing: &HeaderCasing, name: &'static str) -> String {
+        let mut out = Vec::new();
+        casing
+            .write_name(&mut out, &HeaderName::from_static(name))
+            .unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_write_name() {
+        let lower = HeaderCasing::Lowercase;
+        assert_eq!(write(&lower, "content-length"), "content-length");
+
+        let title = HeaderCasing::TitleCase;
+        assert_eq!(write(&title, "content-length"), "Content-Length");
+        assert_eq!(write(&title, "host"), "Host");
+        assert_eq!(write(&title, "x--a"), "X--A");
+
+        let preserve = HeaderCasing::Preserve(vec!["X-API-key".into()]);
+        assert_eq!(write(&preserve, "x-api-key"), "X-API-key");
+        assert_eq!(write(&preserve, "host"), "host");
+    }
+}
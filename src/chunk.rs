@@ -1,10 +1,47 @@
 use core::str;
 
+use http::{HeaderMap, HeaderName, HeaderValue};
+
 use crate::util::find_crlf;
-use crate::Error;
+use crate::{Error, Limits, ParsePolicy};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Dechunker {
+    state: State,
+    extensions: Vec<ChunkExtension>,
+    trailers: HeaderMap,
+    trailers_size: usize,
+    max_size_len: usize,
+    ignore_invalid_extensions: bool,
+    max_trailer_size: usize,
+    max_line_len: usize,
+    max_extensions: usize,
+}
+
+/// A chunk extension parameter.
+///
+/// With `transfer-encoding: chunked`, each chunk size can be followed by extension
+/// parameters, i.e. `5;name=value;other\r\n`. Quoted values are unescaped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChunkExtension {
+    name: String,
+    value: Option<String>,
+}
+
+impl ChunkExtension {
+    /// The extension name.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The extension value, if there is one.
+    pub fn value(&self) -> Option<&str> {
+        self.value.as_deref()
+    }
+}
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub(crate) enum Dechunker {
+enum State {
     Size,
     Chunk(usize),
     CrLf,
@@ -20,8 +57,18 @@ struct Pos {
 }
 
 impl Dechunker {
-    pub fn new() -> Self {
-        Dechunker::Size
+    pub fn new(policy: &ParsePolicy, limits: &Limits) -> Self {
+        Dechunker {
+            state: State::Size,
+            extensions: Vec::new(),
+            trailers: HeaderMap::new(),
+            trailers_size: 0,
+            max_size_len: policy.max_chunk_size_len,
+            ignore_invalid_extensions: policy.ignore_invalid_chunk_extensions,
+            max_trailer_size: limits.max_trailer_size,
+            max_line_len: limits.max_line_len,
+            max_extensions: limits.max_chunk_extensions,
+        }
     }
 
     pub fn parse_input(&mut self, src: &[u8], dst: &mut [u8]) -> Result<(usize, usize), Error> {
@@ -31,13 +78,13 @@ impl Dechunker {
         };
 
         loop {
-            let more = match self {
-                Dechunker::Size => self.read_size(src, &mut pos)?,
-                Dechunker::Chunk(_) => self.read_data(src, dst, &mut pos)?,
-                Dechunker::CrLf => self.expect_crlf(src, &mut pos)?,
-                Dechunker::Ending => self.trailer_or_ended(src, &mut pos)?,
-                Dechunker::Trailer => self.trailer(src, &mut pos)?,
-                Dechunker::Ended => false,
+            let more = match self.state {
+                State::Size => self.read_size(src, &mut pos)?,
+                State::Chunk(_) => self.read_data(src, dst, &mut pos)?,
+                State::CrLf => self.expect_crlf(src, &mut pos)?,
+                State::Ending => self.trailer_or_ended(src, &mut pos)?,
+                State::Trailer => self.trailer(src, &mut pos)?,
+                State::Ended => false,
             };
 
             if !more {
@@ -49,20 +96,32 @@ impl Dechunker {
     }
 
     pub fn is_on_chunk_boundary(&self) -> bool {
-        *self == Self::Size
+        self.state == State::Size
     }
 
     #[cfg(test)]
     fn left(&self) -> usize {
-        if let Self::Chunk(l) = self {
-            *l
+        if let State::Chunk(l) = self.state {
+            l
         } else {
             0
         }
     }
 
     pub fn is_ended(&self) -> bool {
-        matches!(self, Self::Ended)
+        matches!(self.state, State::Ended)
+    }
+
+    /// The extensions of the chunk being read, or the one last read.
+    pub fn extensions(&self) -> &[ChunkExtension] {
+        &self.extensions
+    }
+
+    /// The trailer fields sent after the last chunk.
+    ///
+    /// Only complete once the dechunker is ended.
+    pub fn trailers(&self) -> &HeaderMap {
+        &self.trailers
     }
 
     fn read_size(&mut self, src: &[u8], pos: &mut Pos) -> Result<bool, Error> {
@@ -70,29 +129,47 @@ impl Dechunker {
 
         let i = match find_crlf(src) {
             Some(v) => v,
+            // Allow for the \r of a line that is not yet ended.
+            None if src.len() > self.max_line_len + 1 => {
+                return Err(Error::ChunkLineTooLong(self.max_line_len))
+            }
             None => return Ok(false),
         };
 
-        const SANITY_CHECK: usize = 20;
+        if i > self.max_line_len {
+            return Err(Error::ChunkLineTooLong(self.max_line_len));
+        }
+
+        let line = &src[..i];
+        let maybe_meta = line.iter().position(|c| *c == b';');
+        let len_end = maybe_meta.unwrap_or(i);
 
         // Some sanity check for how long the chunk length is
-        if i > SANITY_CHECK {
+        if len_end > self.max_size_len {
             return Err(Error::ChunkExpectedCrLf);
         }
-        let maybe_meta = src.iter().take(100).position(|c| *c == b';');
 
-        let len_end = maybe_meta.unwrap_or(SANITY_CHECK + 1).min(i);
-        let len_str = str::from_utf8(&src[..len_end])
+        let len_str = str::from_utf8(&line[..len_end])
             .map_err(|_| Error::ChunkLenNotAscii)?
             .trim();
 
         let len = usize::from_str_radix(len_str, 16).map_err(|_| Error::ChunkLenNotANumber)?;
 
+        self.extensions.clear();
+        if let Some(meta) = maybe_meta {
+            match parse_extensions(&line[meta + 1..], self.max_extensions, &mut self.extensions) {
+                Err(Error::ChunkExtensionInvalid) if self.ignore_invalid_extensions => {
+                    self.extensions.clear();
+                }
+                r => r?,
+            }
+        }
+
         pos.index_in += i + 2;
-        *self = if len == 0 {
-            Self::Ending
+        self.state = if len == 0 {
+            State::Ending
         } else {
-            Self::Chunk(len)
+            State::Chunk(len)
         };
 
         Ok(true)
@@ -102,8 +179,8 @@ impl Dechunker {
         let src = &src[pos.index_in..];
         let dst = &mut dst[pos.index_out..];
 
-        let left = match self {
-            Self::Chunk(v) => v,
+        let left = match &mut self.state {
+            State::Chunk(v) => v,
             _ => unreachable!(),
         };
 
@@ -116,7 +193,7 @@ impl Dechunker {
         *left -= to_read;
 
         if *left == 0 {
-            *self = Self::CrLf;
+            self.state = State::CrLf;
         }
 
         Ok(to_read > 0)
@@ -135,7 +212,7 @@ impl Dechunker {
         }
 
         pos.index_in += 2;
-        *self = Self::Size;
+        self.state = State::Size;
 
         Ok(false)
     }
@@ -145,15 +222,18 @@ impl Dechunker {
 
         let i = match find_crlf(src) {
             Some(v) => v,
-            None => return Ok(false),
+            None => {
+                self.check_trailers_size(src.len())?;
+                return Ok(false);
+            }
         };
 
         if i == 0 {
             pos.index_in += 2;
-            *self = Self::Ended;
+            self.state = State::Ended;
         } else {
             // Non-crlf before
-            *self = Self::Trailer;
+            self.state = State::Trailer;
         }
 
         Ok(true)
@@ -164,16 +244,149 @@ impl Dechunker {
 
         let i = match find_crlf(src) {
             Some(v) => v,
-            None => return Ok(false),
+            None => {
+                // Stop before buffering too much of an incomplete trailer line.
+                self.check_trailers_size(src.len())?;
+                return Ok(false);
+            }
         };
         assert!(i > 0);
 
+        self.check_trailers_size(i + 2)?;
+        self.trailers_size += i + 2;
+
+        let (name, value) = parse_trailer_line(&src[..i])?;
+        self.trailers.append(name, value);
+
         // advance the trailer, and 2 for the crlf.
         pos.index_in += i + 2;
-        *self = Self::Ending;
+        self.state = State::Ending;
 
         Ok(true)
     }
+
+    fn check_trailers_size(&self, more: usize) -> Result<(), Error> {
+        if self.trailers_size + more > self.max_trailer_size {
+            return Err(Error::TrailersTooLarge(self.max_trailer_size));
+        }
+        Ok(())
+    }
+}
+
+/// Parse chunk extensions, the part after the first `;` up to the crlf.
+///
+/// ```text
+/// chunk-ext      = *( BWS ";" BWS chunk-ext-name
+///                     [ BWS "=" BWS chunk-ext-val ] )
+/// chunk-ext-name = token
+/// chunk-ext-val  = token / quoted-string
+/// ```
+fn parse_extensions(
+    mut src: &[u8],
+    max: usize,
+    extensions: &mut Vec<ChunkExtension>,
+) -> Result<(), Error> {
+    loop {
+        if extensions.len() == max {
+            return Err(Error::TooManyChunkExtensions(max));
+        }
+
+        src = trim_bws(src);
+
+        let (name, rest) = split_token(src);
+        if name.is_empty() {
+            return Err(Error::ChunkExtensionInvalid);
+        }
+        src = trim_bws(rest);
+
+        let value = if let Some(rest) = src.strip_prefix(b"=") {
+            let rest = trim_bws(rest);
+            let (value, rest) = if rest.first() == Some(&b'"') {
+                split_quoted(rest)?
+            } else {
+                let (token, rest) = split_token(rest);
+                if token.is_empty() {
+                    return Err(Error::ChunkExtensionInvalid);
+                }
+                (token.to_vec(), rest)
+            };
+            src = trim_bws(rest);
+            Some(String::from_utf8(value).map_err(|_| Error::ChunkExtensionInvalid)?)
+        } else {
+            None
+        };
+
+        extensions.push(ChunkExtension {
+            // token chars are ascii
+            name: String::from_utf8_lossy(name).to_string(),
+            value,
+        });
+
+        match src.split_first() {
+            None => return Ok(()),
+            Some((b';', rest)) => src = rest,
+            Some(_) => return Err(Error::ChunkExtensionInvalid),
+        }
+    }
+}
+
+fn trim_bws(src: &[u8]) -> &[u8] {
+    let start = src
+        .iter()
+        .position(|c| *c != b' ' && *c != b'\t')
+        .unwrap_or(src.len());
+    &src[start..]
+}
+
+fn split_token(src: &[u8]) -> (&[u8], &[u8]) {
+    let is_tchar = |c: &u8| c.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(c);
+    let end = src.iter().position(|c| !is_tchar(c)).unwrap_or(src.len());
+    src.split_at(end)
+}
+
+/// Split a quoted-string (including quotes) from the start of src, unescaping it.
+fn split_quoted(src: &[u8]) -> Result<(Vec<u8>, &[u8]), Error> {
+    let mut value = Vec::new();
+    let mut iter = src.iter().enumerate().skip(1);
+
+    while let Some((i, c)) = iter.next() {
+        match c {
+            b'"' => return Ok((value, &src[i + 1..])),
+            b'\\' => {
+                let (_, c) = iter.next().ok_or(Error::ChunkExtensionInvalid)?;
+                value.push(*c);
+            }
+            c if c.is_ascii_control() && *c != b'\t' => return Err(Error::ChunkExtensionInvalid),
+            c => value.push(*c),
+        }
+    }
+
+    // Missing end quote
+    Err(Error::ChunkExtensionInvalid)
+}
+
+/// Parse a single `name: value` trailer field line (without the crlf).
+fn parse_trailer_line(line: &[u8]) -> Result<(HeaderName, HeaderValue), Error> {
+    let bad_trailer = || Error::BadHeader(String::from_utf8_lossy(line).to_string());
+
+    let colon = line
+        .iter()
+        .position(|c| *c == b':')
+        .ok_or_else(bad_trailer)?;
+
+    // No whitespace is allowed between the field name and colon, which
+    // HeaderName rejects for us.
+    let name = HeaderName::from_bytes(&line[..colon]).map_err(|_| bad_trailer())?;
+
+    let is_ows = |c: &u8| *c == b' ' || *c == b'\t';
+    let value = &line[colon + 1..];
+    let start = value.iter().position(|c| !is_ows(c)).unwrap_or(value.len());
+    let end = value.iter().rposition(|c| !is_ows(c)).map(|p| p + 1);
+    let value = &value[start..end.unwrap_or(start)];
+
+    let value = HeaderValue::from_bytes(value).map_err(|_| bad_trailer())?;
+
+    Ok((name, value))
 }
 
 #[cfg(test)]
@@ -182,7 +395,7 @@ mod test {
 
     #[test]
     fn test_dechunk_size() -> Result<(), Error> {
-        let mut d = Dechunker::new();
+        let mut d = Dechunker::new(&ParsePolicy::default(), &Limits::default());
         let mut b = [0; 1024];
         assert_eq!(d.parse_input(b"", &mut b)?, (0, 0));
         assert_eq!(d.parse_input(b"2", &mut b)?, (0, 0));
@@ -195,16 +408,132 @@ mod test {
 
     #[test]
     fn test_dechunk_size_meta() -> Result<(), Error> {
-        let mut d = Dechunker::new();
+        let mut d = Dechunker::new(&ParsePolicy::default(), &Limits::default());
         let mut b = [0; 1024];
         assert_eq!(d.parse_input(b"2;meta\r", &mut b)?, (0, 0));
         assert_eq!(d.parse_input(b"2;meta\r\n", &mut b)?, (8, 0));
         Ok(())
     }
 
+    #[test]
+    fn test_dechunk_extensions() -> Result<(), Error> {
+        let mut d = Dechunker::new(&ParsePolicy::default(), &Limits::default());
+        let mut b = [0; 1024];
+        const DATA: &[u8] = b"2 ; a=1;b ; c = \"x; \\\"y\\\"\"\r\nOK\r\n";
+        assert_eq!(d.parse_input(DATA, &mut b)?, (DATA.len(), 2));
+
+        let ext = d.extensions();
+        assert_eq!(ext.len(), 3);
+        assert_eq!((ext[0].name(), ext[0].value()), ("a", Some("1")));
+        assert_eq!((ext[1].name(), ext[1].value()), ("b", None));
+        assert_eq!((ext[2].name(), ext[2].value()), ("c", Some("x; \"y\"")));
+
+        // Next chunk without extensions clears them.
+        assert_eq!(d.parse_input(b"2\r\nOK\r\n", &mut b)?, (7, 2));
+        assert!(d.extensions().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_dechunk_extensions_invalid() {
+        let mut b = [0; 1024];
+        for data in [&b"2;\r\n"[..], b"2;a=\r\n", b"2;a=\"x\r\n", b"2;a b\r\n"] {
+            let mut d = Dechunker::new(&ParsePolicy::strict(), &Limits::default());
+            let r = d.parse_input(data, &mut b);
+            assert_eq!(r, Err(Error::ChunkExtensionInvalid), "{:?}", data);
+
+            // Ignored by the lenient policy.
+            let mut d = Dechunker::new(&ParsePolicy::lenient(), &Limits::default());
+            let r = d.parse_input(data, &mut b);
+            assert_eq!(r, Ok((data.len(), 0)), "{:?}", data);
+            assert!(d.extensions().is_empty());
+        }
+    }
+
+    #[test]
+    fn test_dechunk_size_line_too_long() {
+        let mut b = [0; 1024];
+        let limits = Limits::new().max_line_len(10);
+
+        let mut d = Dechunker::new(&ParsePolicy::strict(), &limits);
+        let data = b"1;a=xxxxx\r\nx";
+        assert_eq!(d.parse_input(data, &mut b), Ok((data.len(), 1)));
+
+        let mut d = Dechunker::new(&ParsePolicy::strict(), &limits);
+        let data = b"1;a=xxxxxxx\r\nx";
+        assert_eq!(
+            d.parse_input(data, &mut b),
+            Err(Error::ChunkLineTooLong(10))
+        );
+
+        // Still waiting for the end of the line.
+        let mut line = b"1;a=".to_vec();
+        line.extend(std::iter::repeat(b'x').take(1_000_000));
+        let mut d = Dechunker::new(&ParsePolicy::strict(), &Limits::default());
+        assert_eq!(
+            d.parse_input(&line, &mut b),
+            Err(Error::ChunkLineTooLong(8 * 1024))
+        );
+    }
+
+    #[test]
+    fn test_dechunk_too_many_extensions() {
+        let mut b = [0; 1024];
+        let limits = Limits::new().max_chunk_extensions(2);
+
+        let mut d = Dechunker::new(&ParsePolicy::default(), &limits);
+        let data = b"1;a;b\r\nx";
+        assert_eq!(d.parse_input(data, &mut b), Ok((data.len(), 1)));
+
+        let mut d = Dechunker::new(&ParsePolicy::default(), &limits);
+        let data = b"1;a;b;c\r\nx";
+        assert_eq!(
+            d.parse_input(data, &mut b),
+            Err(Error::TooManyChunkExtensions(2))
+        );
+    }
+
+    #[test]
+    fn test_dechunk_size_policy() {
+        let mut b = [0; 1024];
+        let data = b"00000000000000005\r\n";
+
+        let mut d = Dechunker::new(&ParsePolicy::lenient(), &Limits::default());
+        assert_eq!(d.parse_input(data, &mut b), Ok((data.len(), 0)));
+        assert_eq!(d.left(), 5);
+
+        let mut d = Dechunker::new(&ParsePolicy::strict(), &Limits::default());
+        assert_eq!(d.parse_input(data, &mut b), Err(Error::ChunkExpectedCrLf));
+    }
+
+    #[test]
+    fn test_dechunk_trailers_too_large() {
+        let mut b = [0; 1024];
+        let limits = Limits::new().max_trailer_size(20);
+
+        let mut d = Dechunker::new(&ParsePolicy::default(), &limits);
+        let data = b"0\r\nx-a: 12345678\r\n\r\n";
+        assert_eq!(d.parse_input(data, &mut b), Ok((data.len(), 0)));
+
+        let mut d = Dechunker::new(&ParsePolicy::default(), &limits);
+        let data = b"0\r\nx-a: 12345678\r\nx-b: 1\r\n\r\n";
+        assert_eq!(
+            d.parse_input(data, &mut b),
+            Err(Error::TrailersTooLarge(20))
+        );
+
+        // Incomplete line
+        let mut d = Dechunker::new(&ParsePolicy::default(), &limits);
+        let data = b"0\r\nx-a: 12345678901234567890";
+        assert_eq!(
+            d.parse_input(data, &mut b),
+            Err(Error::TrailersTooLarge(20))
+        );
+    }
+
     #[test]
     fn test_dechunk_size_not_meta() -> Result<(), Error> {
-        let mut d = Dechunker::new();
+        let mut d = Dechunker::new(&ParsePolicy::default(), &Limits::default());
         let mut b = [0; 1024];
         assert_eq!(d.parse_input(b"9\r\nnot meta;\r\n", &mut b)?, (14, 9));
         assert_eq!(String::from_utf8_lossy(&b[..9]), "not meta;");
@@ -213,7 +542,7 @@ mod test {
 
     #[test]
     fn test_dechunk_data() -> Result<(), Error> {
-        let mut d = Dechunker::new();
+        let mut d = Dechunker::new(&ParsePolicy::default(), &Limits::default());
         let mut b = [0; 1024];
         assert_eq!(d.parse_input(b"2\r\nOK", &mut b)?, (5, 2));
         assert_eq!(&b[..2], b"OK");
@@ -230,7 +559,7 @@ mod test {
 
     #[test]
     fn test_dechunk_one_chunk_at_a_time() -> Result<(), Error> {
-        let mut d = Dechunker::new();
+        let mut d = Dechunker::new(&ParsePolicy::default(), &Limits::default());
         let mut b = [0; 1024];
         const DATA: &[u8] = b"4\r\ndata\r\n4\r\nmoar\r\n";
         assert_eq!(d.parse_input(DATA, &mut b)?, (9, 4));
@@ -241,4 +570,26 @@ mod test {
         assert_eq!(String::from_utf8_lossy(&b[..4]), "data");
         Ok(())
     }
+
+    #[test]
+    fn test_dechunk_trailers() -> Result<(), Error> {
+        let mut d = Dechunker::new(&ParsePolicy::default(), &Limits::default());
+        let mut b = [0; 1024];
+        const DATA: &[u8] = b"2\r\nOK\r\n0\r\ngrpc-status: 0\r\nDigest:  sha-256=abc \r\n\r\n";
+        assert_eq!(d.parse_input(DATA, &mut b)?, (7, 2));
+        assert_eq!(d.parse_input(&DATA[7..], &mut b)?, (DATA.len() - 7, 0));
+        assert!(d.is_ended());
+        assert_eq!(d.trailers().len(), 2);
+        assert_eq!(d.trailers().get("grpc-status").unwrap(), "0");
+        assert_eq!(d.trailers().get("digest").unwrap(), "sha-256=abc");
+        Ok(())
+    }
+
+    #[test]
+    fn test_dechunk_bad_trailer() {
+        let mut d = Dechunker::new(&ParsePolicy::default(), &Limits::default());
+        let mut b = [0; 1024];
+        let r = d.parse_input(b"0\r\nno colon\r\n\r\n", &mut b);
+        assert_eq!(r, Err(Error::BadHeader("no colon".to_string())));
+    }
 }
@@ -1,5 +1,7 @@
+use std::borrow::Cow;
 use std::mem;
 
+use http::uri::Scheme;
 use http::{HeaderMap, HeaderName, HeaderValue, Method, Request, Uri, Version};
 use url::Url;
 
@@ -34,12 +36,16 @@ use super::MAX_EXTRA_HEADERS;
 /// 8.  `Accept` header.
 /// 9.  Changing the `Method` when following redirects.
 /// 10. Changing the `Uri` when following redirect.
+/// 11. Sending the request target in absolute-form when going via a proxy.
+/// 12. The order of the headers on the wire.
 ///
 pub(crate) struct AmendedRequest<Body> {
     request: Request<Option<Body>>,
     uri: Option<Uri>,
+    absolute_form: bool,
     headers: ArrayVec<(HeaderName, HeaderValue), MAX_EXTRA_HEADERS>,
-    unset: ArrayVec<HeaderName, 3>,
+    unset: ArrayVec<HeaderName, 4>,
+    order: Vec<HeaderName>,
 }
 
 impl<Body> AmendedRequest<Body> {
@@ -52,8 +58,10 @@ impl<Body> AmendedRequest<Body> {
         AmendedRequest {
             request: Request::from_parts(parts, Some(body)),
             uri: None,
+            absolute_form: false,
             headers: ArrayVec::from_fn(|_| (UNINIT_NAME, UNINIT_VALUE)),
             unset: ArrayVec::from_fn(|_| UNINIT_NAME),
+            order: Vec::new(),
         }
     }
 
@@ -75,16 +83,63 @@ impl<Body> AmendedRequest<Body> {
         }
     }
 
-    pub fn prelude(&self) -> (&Method, &str, Version) {
+    pub fn prelude(&self) -> (&Method, Cow<'_, str>, Version) {
         let r = &self.request;
-        (
-            r.method(),
-            self.uri()
-                .path_and_query()
-                .map(|p| p.as_str())
-                .unwrap_or("/"),
-            r.version(),
-        )
+
+        let target = if r.method() == Method::CONNECT {
+            // unwrap is ok because analyze() ensures CONNECT has an authority.
+            Cow::Owned(self.authority_form().unwrap())
+        } else if self.absolute_form {
+            Cow::Owned(self.absolute_form())
+        } else {
+            Cow::Borrowed(
+                self.uri()
+                    .path_and_query()
+                    .map(|p| p.as_str())
+                    .unwrap_or("/"),
+            )
+        };
+
+        (r.method(), target, r.version())
+    }
+
+    pub fn set_absolute_form(&mut self) {
+        self.absolute_form = true;
+    }
+
+    /// The `scheme://host:port/path` request target used via a proxy.
+    ///
+    /// Any userinfo in the uri is left out.
+    fn absolute_form(&self) -> String {
+        let uri = self.uri();
+
+        // unwrap is ok because Flow::send_via_proxy() ensures an absolute uri.
+        let scheme = uri.scheme_str().unwrap();
+        let host = uri.host().unwrap();
+
+        let port = uri.port().map(|p| format!(":{}", p)).unwrap_or_default();
+        let path = uri.path_and_query().map(|p| p.as_str()).unwrap_or("/");
+
+        format!("{}://{}{}{}", scheme, host, port, path)
+    }
+
+    /// The `host:port` request target used by CONNECT.
+    ///
+    /// Without an explicit port, the port is derived from the scheme, defaulting
+    /// to 443 since CONNECT is mostly used to tunnel TLS.
+    pub fn authority_form(&self) -> Option<String> {
+        let uri = self.uri();
+        let host = uri.host()?;
+
+        let port = uri.port_u16().unwrap_or_else(|| {
+            if uri.scheme() == Some(&Scheme::HTTP) {
+                80
+            } else {
+                443
+            }
+        });
+
+        Some(format!("{}:{}", host, port))
     }
 
     pub fn set_header<K, V>(&mut self, name: K, value: V) -> Result<(), Error>
@@ -116,11 +171,36 @@ impl<Body> AmendedRequest<Body> {
         Ok(())
     }
 
+    /// Headers with these names are written first, in this order.
+    pub fn set_header_order(&mut self, order: Vec<HeaderName>) {
+        self.order.clear();
+        for name in order {
+            // Each name only once, or the headers would be repeated.
+            if !self.order.contains(&name) {
+                self.order.push(name);
+            }
+        }
+    }
+
     pub fn original_request_headers(&self) -> &HeaderMap {
         self.request.headers()
     }
 
     pub fn headers(&self) -> impl Iterator<Item = (&HeaderName, &HeaderValue)> {
+        // Several values for the same name keep their relative order.
+        let ordered = self
+            .order
+            .iter()
+            .flat_map(move |name| self.unordered_headers().filter(move |v| v.0 == name));
+
+        let rest = self
+            .unordered_headers()
+            .filter(move |v| !self.order.contains(v.0));
+
+        ordered.chain(rest)
+    }
+
+    fn unordered_headers(&self) -> impl Iterator<Item = (&HeaderName, &HeaderValue)> {
         self.headers
             .iter()
             .map(|v| (&v.0, &v.1))
@@ -186,6 +266,14 @@ impl<Body> AmendedRequest<Body> {
 
         m.verify_version(v)?;
 
+        for (name, value) in self.headers() {
+            check_header(name.as_str(), value.as_bytes())?;
+        }
+
+        if *m == Method::CONNECT && self.uri().host().is_none() {
+            return Err(Error::ConnectMissingAuthority);
+        }
+
         let count_host = self.headers_get_all("host").count();
         if count_host > 1 {
             return Err(Error::TooManyHostHeaders);
@@ -259,3 +347,53 @@ pub(crate) struct RequestInfo {
     pub req_host_header: bool,
     pub req_body_header: bool,
 }
+
+/// Reject a header that could break the message framing when written.
+///
+/// `HeaderName` and `HeaderValue` are validated when created through the safe
+/// constructors, but this is the last check before the bytes go on the wire.
+fn check_header(name: &str, value: &[u8]) -> Result<(), Error> {
+    let is_tchar = |c: u8| c.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&c);
+
+    if name.is_empty() || !name.bytes().all(is_tchar) {
+        return Err(Error::BadHeader(format!("invalid header name: {:?}", name)));
+    }
+
+    if let Some(c) = value.iter().find(|c| matches!(c, b'\r' | b'\n' | b'\0')) {
+        return Err(Error::BadHeader(format!(
+            "header {} contains {:?}",
+            name, *c as char
+        )));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_check_header() {
+        assert_eq!(check_header("x-a", b"ok\tvalue"), Ok(()));
+        // obs-text doesn't break framing
+        assert_eq!(check_header("x-a", b"caf\xe9"), Ok(()));
+
+        assert_eq!(
+            check_header("x-a", b"one\r\nx-injected: 1"),
+            Err(Error::BadHeader("header x-a contains '\\r'".into()))
+        );
+        assert_eq!(
+            check_header("x-a", b"one\ntwo"),
+            Err(Error::BadHeader("header x-a contains '\\n'".into()))
+        );
+        assert_eq!(
+            check_header("x-a", b"nul\0"),
+            Err(Error::BadHeader("header x-a contains '\\0'".into()))
+        );
+        assert_eq!(
+            check_header("x a", b"v"),
+            Err(Error::BadHeader("invalid header name: \"x a\"".into()))
+        );
+    }
+}
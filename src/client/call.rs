@@ -1,18 +1,21 @@
 //! A single request-response. No redirection or other logic.
 
+use std::borrow::Cow;
 use std::fmt;
 use std::io::Write;
 use std::marker::PhantomData;
 
-use http::{HeaderName, HeaderValue, Method, Request, Response, StatusCode, Version};
+use http::{HeaderMap, HeaderName, HeaderValue, Method, Request, Response, StatusCode, Version};
 
-use crate::body::{BodyReader, BodyWriter};
-use crate::parser::{try_parse_partial_response, try_parse_response};
-use crate::util::{log_data, Writer};
-use crate::{BodyMode, Error};
+use crate::body::{check_announced_trailers, BodyReader, BodyWriter};
+use crate::parser::{
+    try_parse_partial_response_with, try_parse_response_with, try_parse_status, HeadScanner,
+};
+use crate::redact::RedactedHeaders;
+use crate::util::{do_write_headers, log_data, Writer};
+use crate::{BodyMode, ChunkExtension, Error, HeaderCasing, Limits, ParsePolicy};
 
 use super::amended::AmendedRequest;
-use super::MAX_RESPONSE_HEADERS;
 
 #[doc(hidden)]
 pub mod state {
@@ -111,7 +114,14 @@ impl<State, B> Call<State, B> {
             .analyze(self.state.writer, self.state.skip_method_body_check)?;
 
         if !info.req_host_header {
-            if let Some(host) = self.request.uri().host() {
+            if *self.request.method() == Method::CONNECT {
+                // CONNECT sends the same host:port in the host header as the request target.
+                // unwrap is ok because analyze() ensures CONNECT has an authority.
+                let authority = self.request.authority_form().unwrap();
+                let host = HeaderValue::from_str(&authority)
+                    .map_err(|e| Error::BadHeader(e.to_string()))?;
+                self.request.set_header("Host", host)?;
+            } else if let Some(host) = self.request.uri().host() {
                 // User did not set a host header, and there is one in uri, we set that.
                 // We need an owned value to set the host header.
                 let host =
@@ -122,7 +132,7 @@ impl<State, B> Call<State, B> {
 
         if !info.req_body_header && info.body_mode.has_body() {
             // User did not set a body header, we set one.
-            let header = info.body_mode.body_header();
+            let header = info.body_mode.body_header()?;
             self.request.set_header(header.0, header.1)?;
         }
 
@@ -159,9 +169,51 @@ impl<State, B> Call<State, B> {
     pub(crate) fn body_mode(&self) -> BodyMode {
         self.state
             .reader
+            .as_ref()
             .map(|r| r.body_mode())
             .unwrap_or(BodyMode::Chunked)
     }
+
+    /// Set how strictly the response is parsed.
+    ///
+    /// Defaults to [`ParsePolicy::lenient()`].
+    pub fn set_parse_policy(&mut self, policy: ParsePolicy) {
+        self.state.policy = policy;
+    }
+
+    /// Set the limits on received headers.
+    ///
+    /// Defaults to [`Limits::new()`].
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.state.limits = limits;
+    }
+
+    /// Set how header names are written.
+    ///
+    /// Defaults to [`HeaderCasing::Lowercase`].
+    pub fn set_header_casing(&mut self, casing: HeaderCasing) {
+        self.state.casing = casing;
+    }
+
+    /// Set the headers with values masked in the trace logging.
+    ///
+    /// Defaults to `authorization`, `proxy-authorization`, `cookie` and `set-cookie`.
+    pub fn set_redacted_headers(&mut self, names: Vec<HeaderName>) {
+        self.set_redacted(RedactedHeaders::new(names));
+    }
+
+    pub(crate) fn set_redacted(&mut self, redacted: RedactedHeaders) {
+        self.state.redacted = redacted;
+    }
+
+    /// How much of the prelude is written, as `(request line, number of headers)`.
+    pub(crate) fn prelude_progress(&self) -> (bool, usize) {
+        match self.state.phase {
+            Phase::SendLine => (false, 0),
+            Phase::SendHeaders(index) => (true, index),
+            _ => (true, self.request.headers_len()),
+        }
+    }
 }
 
 #[derive(Debug, Default)]
@@ -171,6 +223,11 @@ struct BodyState {
     reader: Option<BodyReader>,
     skip_method_body_check: bool,
     stop_on_chunk_boundary: bool,
+    policy: ParsePolicy,
+    limits: Limits,
+    scanner: HeadScanner,
+    casing: HeaderCasing,
+    redacted: RedactedHeaders,
 }
 
 impl BodyState {
@@ -240,7 +297,7 @@ impl<B> Call<WithoutBody, B> {
     pub fn write(&mut self, output: &mut [u8]) -> Result<usize, Error> {
         self.analyze_request()?;
 
-        let mut w = Writer::new(output);
+        let mut w = Writer::new(output, &self.state.redacted);
         try_write_prelude(&self.request, &mut self.state, &mut w)?;
 
         let output_used = w.len();
@@ -305,7 +362,7 @@ impl<B> Call<WithBody, B> {
     pub fn write(&mut self, input: &[u8], output: &mut [u8]) -> Result<(usize, usize), Error> {
         self.analyze_request()?;
 
-        let mut w = Writer::new(output);
+        let mut w = Writer::new(output, &self.state.redacted);
 
         let mut input_used = 0;
 
@@ -328,6 +385,72 @@ impl<B> Call<WithBody, B> {
         Ok((input_used, output_used))
     }
 
+    /// End a chunked request body with trailer fields
+    ///
+    /// This is instead of signaling the end of the body with an empty input to
+    /// [`Call::write()`]. Every trailer field must be announced by a `trailer`
+    /// request header.
+    ///
+    /// Returns how many bytes of the output that were used. The ending is written
+    /// in one go, and errors with [`Error::OutputOverflow`] if the output is too small.
+    ///
+    /// ```
+    /// # use ureq_proto::client::call::Call;
+    /// # use ureq_proto::http::{HeaderMap, Request};
+    /// let req = Request::post("http://f.test/page")
+    ///     .header("trailer", "digest")
+    ///     .body(())
+    ///     .unwrap();
+    /// let mut call = Call::with_body(req).unwrap();
+    ///
+    /// let mut output = vec![0; 1024];
+    /// let (_, n1) = call.write(&[], &mut output).unwrap(); // send headers
+    /// let (_, n2) = call.write(b"hallo", &mut output[n1..]).unwrap(); // send body
+    ///
+    /// let mut trailers = HeaderMap::new();
+    /// trailers.insert("digest", "sha-256=abc".parse().unwrap());
+    ///
+    /// let n3 = call.write_trailers(&trailers, &mut output[n1 + n2..]).unwrap();
+    /// let s = std::str::from_utf8(&output[n1..n1 + n2 + n3]).unwrap();
+    ///
+    /// assert_eq!(s, "5\r\nhallo\r\n0\r\ndigest: sha-256=abc\r\n\r\n");
+    /// assert!(call.is_finished());
+    /// ```
+    pub fn write_trailers(
+        &mut self,
+        trailers: &HeaderMap,
+        output: &mut [u8],
+    ) -> Result<usize, Error> {
+        self.analyze_request()?;
+
+        if !self.is_body() {
+            return Err(Error::UnfinishedRequest);
+        }
+
+        if !self.state.writer.is_chunked() {
+            return Err(Error::BodyNotChunked);
+        }
+
+        if self.state.writer.is_ended() {
+            return Err(Error::BodyContentAfterFinish);
+        }
+
+        let announced = self
+            .request
+            .headers()
+            .filter(|(k, _)| *k == "trailer")
+            .map(|(_, v)| v);
+        check_announced_trailers(announced, trailers)?;
+
+        let mut w = Writer::new(output, &self.state.redacted);
+
+        if !self.state.writer.write_trailers(trailers, &mut w) {
+            return Err(Error::OutputOverflow);
+        }
+
+        Ok(w.len())
+    }
+
     pub(crate) fn consume_direct_write(&mut self, amount: usize) -> Result<(), Error> {
         if let Some(left) = self.state.writer.left_to_send() {
             if amount as u64 > left {
@@ -409,7 +532,7 @@ fn try_write_prelude_part<Body>(
             let all = request.headers();
             let skipped = all.skip(*index);
 
-            do_write_headers(skipped, index, header_count - 1, w);
+            do_write_headers(skipped, index, header_count - 1, &state.casing, w);
 
             if *index == header_count {
                 state.phase = Phase::SendBody;
@@ -422,33 +545,10 @@ fn try_write_prelude_part<Body>(
     }
 }
 
-fn do_write_send_line(line: (&Method, &str, Version), w: &mut Writer) -> bool {
+fn do_write_send_line(line: (&Method, Cow<'_, str>, Version), w: &mut Writer) -> bool {
     w.try_write(|w| write!(w, "{} {} {:?}\r\n", line.0, line.1, line.2))
 }
 
-fn do_write_headers<'a, I>(headers: I, index: &mut usize, last_index: usize, w: &mut Writer)
-where
-    I: Iterator<Item = (&'a HeaderName, &'a HeaderValue)>,
-{
-    for h in headers {
-        let success = w.try_write(|w| {
-            write!(w, "{}: ", h.0)?;
-            w.write_all(h.1.as_bytes())?;
-            write!(w, "\r\n")?;
-            if *index == last_index {
-                write!(w, "\r\n")?;
-            }
-            Ok(())
-        });
-
-        if success {
-            *index += 1;
-        } else {
-            break;
-        }
-    }
-}
-
 impl<B> Call<RecvResponse, B> {
     /// Try reading response headers
     ///
@@ -457,20 +557,61 @@ impl<B> Call<RecvResponse, B> {
     /// the return value `(usize, Response<()>)` contains how many bytes were consumed
     /// of the `input`.
     ///
+    /// Between calls returning `None`, the `input` must be the same buffer with more bytes
+    /// appended. Only the new bytes are scanned for the end of the headers. An `input`
+    /// shorter than in the previous call starts the scan over.
+    ///
+    /// This is a breaking change from earlier versions, which re-parsed the entire `input`
+    /// on every call and therefore accepted any buffer holding the start of the response.
+    ///
+    /// An interim (1xx) response, such as `100 Continue` or `103 Early Hints`, is also
+    /// returned as `Some`. It is followed by the final response, so [`Call::is_finished()`]
+    /// stays `false`, and `try_response()` should be called again with the remaining input.
+    ///
     /// Once the response headers are succesfully read, use [`Call::into_body()`] to proceed
     /// reading the response body.
     pub fn try_response(&mut self, input: &[u8]) -> Result<Option<(usize, Response<()>)>, Error> {
-        // ~3k for 100 headers
-        let (input_used, response) = match try_parse_response::<MAX_RESPONSE_HEADERS>(input)? {
+        let policy = &self.state.policy;
+        let limits = &self.state.limits;
+
+        let too_many_headers = |e| match e {
+            Error::HttpParseTooManyHeaders => Error::TooManyHeaders(limits.max_headers),
+            e => e,
+        };
+
+        let head_end = self.state.scanner.scan(input, limits)?;
+
+        // ~3k for 100 headers. Only allocated when there is something to parse.
+        let new_headers = || vec![httparse::EMPTY_HEADER; limits.max_headers];
+
+        let parsed = match head_end {
+            // Only parse once we know all headers are there.
+            Some(end) => try_parse_response_with(&input[..end], policy, &mut new_headers())
+                .map_err(too_many_headers)?,
+            None => None,
+        };
+
+        let (input_used, response) = match parsed {
             Some(v) => v,
             None => {
-                // TODO(martin): I don't like this code. The mission is to be correct HTTP/1.1
-                // and this is a hack to allow for broken servers.
-                //
                 // As a special case, to handle broken servers that does a redirect without
                 // the final trailing \r\n, we try parsing the response as partial, and
-                // if it is a redirect, we can allow the request to continue.
-                if let Some(mut r) = try_parse_partial_response::<MAX_RESPONSE_HEADERS>(input)? {
+                // if it is a redirect, we can allow the request to continue. The location
+                // header must be complete, so there is no point trying mid-line.
+                if !policy.allow_partial_redirect || !input.ends_with(b"\n") {
+                    return Ok(None);
+                }
+
+                // No point parsing the headers of anything but a redirect.
+                let is_redirection = try_parse_status(input).map(|s| s.is_redirection());
+                if is_redirection != Some(true) {
+                    return Ok(None);
+                }
+
+                let partial = try_parse_partial_response_with(input, &mut new_headers())
+                    .map_err(too_many_headers)?;
+
+                if let Some(mut r) = partial {
                     // A redirection must have a location header.
                     let is_complete_redirection =
                         r.status().is_redirection() && r.headers().contains_key("location");
@@ -492,7 +633,7 @@ impl<B> Call<RecvResponse, B> {
             }
         };
 
-        log_data(&input[..input_used]);
+        log_data(&input[..input_used], &self.state.redacted);
 
         let http10 = response.version() == Version::HTTP_10;
         let status = response.status().as_u16();
@@ -506,15 +647,19 @@ impl<B> Call<RecvResponse, B> {
             return Ok(Some((input_used, response)));
         }
 
-        let header_lookup = |name: &str| {
-            if let Some(header) = response.headers().get(name) {
-                return header.to_str().ok();
-            }
-            None
-        };
+        if is_interim(response.status()) {
+            // Such as 102 Processing or 103 Early Hints. The final response follows.
+            return Ok(Some((input_used, response)));
+        }
 
-        let recv_body_mode =
-            BodyReader::for_response(http10, self.request.method(), status, &header_lookup)?;
+        let recv_body_mode = BodyReader::for_response(
+            http10,
+            self.request.method(),
+            status,
+            response.headers(),
+            &self.state.policy,
+            &self.state.limits,
+        )?;
 
         self.state.reader = Some(recv_body_mode);
 
@@ -564,6 +709,13 @@ impl<B> Call<RecvResponse, B> {
     }
 }
 
+/// Tell if the status is an interim response, which is followed by the final response.
+///
+/// 101 Switching Protocols is informational, but the final response.
+pub(crate) fn is_interim(status: StatusCode) -> bool {
+    status.is_informational() && status != StatusCode::SWITCHING_PROTOCOLS
+}
+
 impl<B> Call<RecvBody, B> {
     /// Read the input as a response body
     ///
@@ -576,7 +728,12 @@ impl<B> Call<RecvBody, B> {
             return Ok((0, 0));
         }
 
-        rbm.read(input, output, self.state.stop_on_chunk_boundary)
+        rbm.read(
+            input,
+            output,
+            self.state.stop_on_chunk_boundary,
+            &self.state.redacted,
+        )
     }
 
     /// Set whether we are stopping on chunk boundaries.
@@ -602,22 +759,40 @@ impl<B> Call<RecvBody, B> {
         rbm.is_ended()
     }
 
+    /// Tell the call that the connection was closed by the server
+    ///
+    /// Ends a close delimited body. Errors with [`Error::IncompleteBody`] if the response
+    /// body is delimited by `content-length` or chunking, and is not ended.
+    pub fn signal_eof(&mut self) -> Result<(), Error> {
+        let rbm = self.state.reader.as_mut().unwrap();
+        rbm.signal_eof()
+    }
+
     /// Tell if response body is closed delimited
     ///
     /// HTTP/1.0 does not have `content-length` to serialize many requests over the same
     /// socket. Instead it uses socket close to determine the body is finished.
     pub fn is_close_delimited(&self) -> bool {
         let rbm = self.state.reader.as_ref().unwrap();
-        matches!(rbm, BodyReader::CloseDelimited)
+        matches!(rbm, BodyReader::CloseDelimited(_))
     }
 
-    // /// Continue to reading trailer headers
-    // pub fn into_trailer(self) -> Result<Option<Call<'b, Trailer>>, Error> {
-    //     todo!()
-    // }
-}
+    /// Extension parameters of the current chunk
+    ///
+    /// Empty unless the body is chunked and the chunk has extensions.
+    pub fn chunk_extensions(&self) -> &[ChunkExtension] {
+        let rbm = self.state.reader.as_ref().unwrap();
+        rbm.chunk_extensions()
+    }
 
-// pub struct Trailer(());
+    /// Trailer fields sent after a chunked response body
+    ///
+    /// Returns `None` until the chunked body is fully read, or if the body is not chunked.
+    pub fn trailers(&self) -> Option<&HeaderMap> {
+        let rbm = self.state.reader.as_ref().unwrap();
+        rbm.trailers()
+    }
+}
 
 impl<State, B> fmt::Debug for Call<State, B> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
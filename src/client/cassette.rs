@@ -0,0 +1,564 @@
+//! Record and replay of the exact bytes of flows, for deterministic tests.
+//!
+//! A [`CassetteRecorder`] is set as observer on a flow and records the bytes sent and
+//! received. The resulting [`Cassette`] is saved with [`Cassette::to_bytes()`], and later
+//! loaded with [`Cassette::from_bytes()`] to be replayed by a [`Replayer`].
+//!
+//! The replayer drives a flow using the recorded bytes as input, and checks that the
+//! bytes sent are exactly the same as in the recording.
+//!
+//! ```
+//! use ureq_proto::client::cassette::{Cassette, Replayer};
+//! use ureq_proto::client::flow::Flow;
+//! use ureq_proto::http::Request;
+//! use ureq_proto::ParsePolicy;
+//!
+//! let recorded = b"ureq-proto cassette 1\n\
+//!     > 36\nGET /page HTTP/1.1\r\nhost: q.test\r\n\r\n\n\
+//!     < 40\nHTTP/1.1 200 OK\r\ncontent-length: 2\r\n\r\nhi\n";
+//!
+//! let cassette = Cassette::from_bytes(recorded).unwrap();
+//! let mut replayer = Replayer::new(cassette);
+//!
+//! let request = Request::get("http://q.test/page").body(()).unwrap();
+//! let flow = Flow::new(request, ParsePolicy::default()).unwrap();
+//!
+//! let replayed = replayer.replay(flow, &[]).unwrap();
+//!
+//! assert_eq!(replayed.response().status(), 200);
+//! assert_eq!(replayed.body(), b"hi");
+//! assert!(replayer.is_done());
+//! ```
+
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use http::{Response, Uri};
+
+use crate::chunk::Dechunker;
+use crate::{Error, Limits, ParsePolicy};
+
+use super::flow::state::{Cleanup, Prepare, RecvResponse, Redirect, SendBody, Tunnel, Upgrade};
+use super::flow::{Await100Result, Flow, RecvBodyResult, RecvResponseResult, SendRequestResult};
+use super::observer::WireObserver;
+
+const MAGIC: &[u8] = b"ureq-proto cassette 1\n";
+
+/// Size of the output buffer used when replaying.
+const OUTPUT_LEN: usize = 64 * 1024;
+
+/// Error when loading or replaying a [`Cassette`].
+#[derive(Debug, PartialEq, Eq)]
+pub enum CassetteError {
+    /// The serialized cassette is malformed.
+    BadCassette(String),
+
+    /// All interactions in the cassette are already replayed.
+    Ended,
+
+    /// What is sent differs from the recording, at this offset in the sent bytes.
+    ///
+    /// For a chunked request body, the offset is the request head followed by
+    /// the decoded body.
+    Mismatch(usize),
+
+    /// The flow failed when replaying.
+    Flow(Error),
+}
+
+impl From<Error> for CassetteError {
+    fn from(value: Error) -> Self {
+        CassetteError::Flow(value)
+    }
+}
+
+impl std::error::Error for CassetteError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CassetteError::Flow(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for CassetteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CassetteError::BadCassette(v) => write!(f, "bad cassette: {}", v),
+            CassetteError::Ended => write!(f, "no more interactions in cassette"),
+            CassetteError::Mismatch(v) => {
+                write!(f, "sent bytes differ from cassette at offset {}", v)
+            }
+            CassetteError::Flow(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+/// Recorded bytes of a number of requests.
+///
+/// The format is the line `ureq-proto cassette 1`, followed by each interaction.
+/// An interaction is the line `> <len>` followed by the sent bytes and a `\n`, then
+/// the line `< <len>` followed by the received bytes and a `\n`. For HTTP/1.1 this
+/// is mostly readable text, but the format is binary safe.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Cassette {
+    interactions: Vec<Interaction>,
+}
+
+/// The bytes sent and received for one request.
+///
+/// Following a redirect is a new request.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Interaction {
+    sent: Vec<u8>,
+    received: Vec<u8>,
+}
+
+impl Cassette {
+    /// Create an empty cassette.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add an interaction to the end of the cassette.
+    pub fn push(&mut self, interaction: Interaction) {
+        self.interactions.push(interaction);
+    }
+
+    /// The recorded interactions, in order.
+    pub fn interactions(&self) -> &[Interaction] {
+        &self.interactions
+    }
+
+    /// Serialize the cassette, i.e. to save to a file.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = MAGIC.to_vec();
+
+        for i in &self.interactions {
+            write_section(&mut out, b'>', &i.sent);
+            write_section(&mut out, b'<', &i.received);
+        }
+
+        out
+    }
+
+    /// Parse a cassette serialized by [`Cassette::to_bytes()`].
+    pub fn from_bytes(mut input: &[u8]) -> Result<Self, CassetteError> {
+        input = input
+            .strip_prefix(MAGIC)
+            .ok_or_else(|| CassetteError::BadCassette("missing header line".into()))?;
+
+        let mut cassette = Cassette::new();
+
+        while !input.is_empty() {
+            let sent = read_section(&mut input, b'>')?;
+            let received = read_section(&mut input, b'<')?;
+            cassette.push(Interaction::new(sent, received));
+        }
+
+        Ok(cassette)
+    }
+}
+
+impl Interaction {
+    /// Create an interaction from the bytes sent and received.
+    pub fn new(sent: Vec<u8>, received: Vec<u8>) -> Self {
+        Interaction { sent, received }
+    }
+
+    /// The bytes sent, i.e. the request line, headers and encoded body.
+    pub fn sent(&self) -> &[u8] {
+        &self.sent
+    }
+
+    /// The bytes received, i.e. the response head and encoded body.
+    pub fn received(&self) -> &[u8] {
+        &self.received
+    }
+}
+
+fn write_section(out: &mut Vec<u8>, marker: u8, data: &[u8]) {
+    out.push(marker);
+    out.extend_from_slice(format!(" {}\n", data.len()).as_bytes());
+    out.extend_from_slice(data);
+    out.push(b'\n');
+}
+
+fn read_section(input: &mut &[u8], marker: u8) -> Result<Vec<u8>, CassetteError> {
+    let bad = |reason: &str| CassetteError::BadCassette(format!("{}: {}", marker as char, reason));
+
+    let line_end = input
+        .iter()
+        .position(|c| *c == b'\n')
+        .ok_or_else(|| bad("missing line"))?;
+
+    let line = &input[..line_end];
+
+    let len = line
+        .strip_prefix(&[marker, b' '])
+        .and_then(|v| std::str::from_utf8(v).ok())
+        .and_then(|v| v.parse::<usize>().ok())
+        .ok_or_else(|| bad("expected length"))?;
+
+    let rest = &input[line_end + 1..];
+
+    // Written to not overflow on a crafted length.
+    if rest.len() <= len || rest[len] != b'\n' {
+        return Err(bad("truncated data"));
+    }
+
+    *input = &rest[len + 1..];
+
+    Ok(rest[..len].to_vec())
+}
+
+/// Observer recording the bytes of flows into a [`Cassette`].
+///
+/// Set on a flow in the `Prepare` state with
+/// [`Flow::set_observer()`](super::flow::Flow::set_observer). Each request, including
+/// those following redirects, becomes one [`Interaction`]. Clones share the same
+/// recording, which means one clone can be given to the flow while another is kept to
+/// take the cassette.
+///
+/// Data written directly with `consume_direct_write()` is not seen by the flow and
+/// can't be recorded.
+#[derive(Clone, Default)]
+pub struct CassetteRecorder {
+    shared: Arc<Mutex<Cassette>>,
+}
+
+impl CassetteRecorder {
+    /// Create a new recorder.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A copy of what is recorded so far.
+    pub fn cassette(&self) -> Cassette {
+        self.lock().clone()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Cassette> {
+        // A poisoned lock still holds a valid cassette.
+        self.shared.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl WireObserver for CassetteRecorder {
+    fn request_start(&mut self, _uri: &Uri) {
+        self.lock().push(Interaction::default());
+    }
+
+    fn sent(&mut self, data: &[u8]) {
+        if let Some(i) = self.lock().interactions.last_mut() {
+            i.sent.extend_from_slice(data);
+        }
+    }
+
+    fn received(&mut self, data: &[u8]) {
+        if let Some(i) = self.lock().interactions.last_mut() {
+            i.received.extend_from_slice(data);
+        }
+    }
+}
+
+/// Replayer of the interactions in a [`Cassette`].
+///
+/// Each call to [`Replayer::replay()`] uses the next interaction.
+#[derive(Debug)]
+pub struct Replayer {
+    cassette: Cassette,
+    index: usize,
+}
+
+/// The outcome of replaying one interaction.
+pub struct Replayed<B> {
+    response: Response<()>,
+    body: Vec<u8>,
+    next: ReplayEnd<B>,
+}
+
+/// The state of the flow after replaying an interaction.
+pub enum ReplayEnd<B> {
+    /// Follow the redirect with [`Flow::as_new_flow()`] and replay the result.
+    Redirect(Flow<B, Redirect>),
+
+    /// The connection is upgraded to another protocol.
+    Upgrade(Flow<B, Upgrade>),
+
+    /// The connection is a CONNECT tunnel.
+    Tunnel(Flow<B, Tunnel>),
+
+    /// The flow is done.
+    Cleanup(Flow<B, Cleanup>),
+}
+
+impl Replayer {
+    /// Create a replayer starting at the first interaction.
+    pub fn new(cassette: Cassette) -> Self {
+        Replayer { cassette, index: 0 }
+    }
+
+    /// Tell if all interactions are replayed.
+    pub fn is_done(&self) -> bool {
+        self.index == self.cassette.interactions.len()
+    }
+
+    /// Drive the flow through the next interaction.
+    ///
+    /// The request is written and compared to the recorded sent bytes, with `body` as
+    /// the request body. The recorded received bytes are the input when reading the
+    /// response.
+    ///
+    /// A difference in what is sent is [`CassetteError::Mismatch`] with the offset of the
+    /// first differing byte. How a chunked body is split into chunks depends on how it was
+    /// written when recording, which is why a chunked body is compared after decoding.
+    pub fn replay<B>(
+        &mut self,
+        flow: Flow<B, Prepare>,
+        body: &[u8],
+    ) -> Result<Replayed<B>, CassetteError> {
+        let interaction = self
+            .cassette
+            .interactions
+            .get(self.index)
+            .ok_or(CassetteError::Ended)?;
+
+        self.index += 1;
+
+        let mut sent = SentCheck {
+            expected: &interaction.sent,
+            pos: 0,
+        };
+        let mut received: &[u8] = &interaction.received;
+        let mut output = vec![0; OUTPUT_LEN];
+
+        let mut flow = flow.proceed();
+
+        while !flow.can_proceed() {
+            let n = flow.write(&mut output)?;
+            sent.check(&output[..n])?;
+        }
+
+        // unwrap is ok because can_proceed() is true.
+        let mut flow = match flow.proceed()?.unwrap() {
+            SendRequestResult::Await100(mut flow) => {
+                let n = flow.try_read_100(received)?;
+                received = &received[n..];
+
+                match flow.proceed()? {
+                    Await100Result::SendBody(flow) => {
+                        send_body(flow, body, &mut sent, &mut output)?
+                    }
+                    Await100Result::RecvResponse(flow) => flow,
+                }
+            }
+            SendRequestResult::SendBody(flow) => send_body(flow, body, &mut sent, &mut output)?,
+            SendRequestResult::RecvResponse(flow) => flow,
+        };
+
+        sent.finish()?;
+
+        let response = loop {
+            let (n, response) = flow.try_response(received)?;
+            received = &received[n..];
+
+            if let Some(response) = response {
+                break response;
+            }
+
+            if n == 0 {
+                return Err(Error::IncompleteResponse.into());
+            }
+        };
+
+        let mut body = Vec::new();
+
+        // unwrap is ok because there is a response.
+        let next = match flow.proceed().unwrap() {
+            RecvResponseResult::RecvBody(mut flow) => {
+                loop {
+                    if received.is_empty() {
+                        flow.signal_eof()?;
+                        break;
+                    }
+
+                    let (input_used, output_used) = flow.read(received, &mut output)?;
+                    received = &received[input_used..];
+                    body.extend_from_slice(&output[..output_used]);
+
+                    if input_used == 0 && output_used == 0 {
+                        break;
+                    }
+                }
+
+                match flow.proceed().ok_or(Error::IncompleteBody)? {
+                    RecvBodyResult::Redirect(flow) => ReplayEnd::Redirect(flow),
+                    RecvBodyResult::Cleanup(flow) => ReplayEnd::Cleanup(flow),
+                }
+            }
+            RecvResponseResult::Redirect(flow) => ReplayEnd::Redirect(flow),
+            RecvResponseResult::Upgrade(flow) => ReplayEnd::Upgrade(flow),
+            RecvResponseResult::Tunnel(flow) => ReplayEnd::Tunnel(flow),
+            RecvResponseResult::Cleanup(flow) => ReplayEnd::Cleanup(flow),
+        };
+
+        Ok(Replayed {
+            response,
+            body,
+            next,
+        })
+    }
+}
+
+impl<B> Replayed<B> {
+    /// The final response.
+    pub fn response(&self) -> &Response<()> {
+        &self.response
+    }
+
+    /// The decoded response body.
+    pub fn body(&self) -> &[u8] {
+        &self.body
+    }
+
+    /// The flow after the interaction.
+    pub fn into_next(self) -> ReplayEnd<B> {
+        self.next
+    }
+}
+
+fn send_body<B>(
+    mut flow: Flow<B, SendBody>,
+    body: &[u8],
+    sent: &mut SentCheck,
+    output: &mut [u8],
+) -> Result<Flow<B, RecvResponse>, CassetteError> {
+    let is_chunked = flow.is_chunked();
+
+    if is_chunked {
+        sent.check_chunked(body)?;
+    }
+
+    let mut input = body;
+
+    while !input.is_empty() {
+        let (input_used, output_used) = flow.write(input, output)?;
+        if !is_chunked {
+            sent.check(&output[..output_used])?;
+        }
+        input = &input[input_used..];
+    }
+
+    if is_chunked {
+        // Empty input ends the chunked body.
+        flow.write(&[], output)?;
+    }
+
+    Ok(flow.proceed().ok_or(Error::UnfinishedRequest)?)
+}
+
+/// Compares sent bytes to the recording.
+struct SentCheck<'a> {
+    expected: &'a [u8],
+    pos: usize,
+}
+
+impl SentCheck<'_> {
+    fn check(&mut self, data: &[u8]) -> Result<(), CassetteError> {
+        let expected = &self.expected[self.pos..];
+
+        if let Some(i) = data.iter().zip(expected).position(|(a, b)| a != b) {
+            return Err(CassetteError::Mismatch(self.pos + i));
+        }
+
+        if data.len() > expected.len() {
+            return Err(CassetteError::Mismatch(self.expected.len()));
+        }
+
+        self.pos += data.len();
+
+        Ok(())
+    }
+
+    /// Compare the rest of the recording, decoded as a chunked body, to `body`.
+    fn check_chunked(&mut self, body: &[u8]) -> Result<(), CassetteError> {
+        let bad = || CassetteError::BadCassette("sent body is not chunked".into());
+
+        let mut input = &self.expected[self.pos..];
+        let mut decoded = vec![0; input.len()];
+        let mut decoded_len = 0;
+
+        let mut dechunker = Dechunker::new(&ParsePolicy::default(), &Limits::new());
+
+        while !dechunker.is_ended() {
+            let (input_used, output_used) = dechunker
+                .parse_input(input, &mut decoded[decoded_len..])
+                .map_err(|_| bad())?;
+
+            if input_used == 0 && output_used == 0 {
+                return Err(bad());
+            }
+
+            input = &input[input_used..];
+            decoded_len += output_used;
+        }
+
+        let decoded = &decoded[..decoded_len];
+
+        if let Some(i) = body.iter().zip(decoded).position(|(a, b)| a != b) {
+            return Err(CassetteError::Mismatch(self.pos + i));
+        }
+
+        if body.len() != decoded.len() {
+            let i = body.len().min(decoded.len());
+            return Err(CassetteError::Mismatch(self.pos + i));
+        }
+
+        // Anything after the chunked body is left for finish().
+        self.pos = self.expected.len() - input.len();
+
+        Ok(())
+    }
+
+    fn finish(&self) -> Result<(), CassetteError> {
+        if self.pos < self.expected.len() {
+            return Err(CassetteError::Mismatch(self.pos));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let mut cassette = Cassette::new();
+        cassette.push(Interaction::new(b"a\nb".to_vec(), b"".to_vec()));
+        cassette.push(Interaction::new(vec![0, 255], b"> 1\n".to_vec()));
+
+        let bytes = cassette.to_bytes();
+        assert_eq!(Cassette::from_bytes(&bytes).unwrap(), cassette);
+    }
+
+    #[test]
+    fn test_bad_cassette() {
+        let err = |s: &[u8]| Cassette::from_bytes(s).unwrap_err();
+
+        assert!(matches!(err(b"nope"), CassetteError::BadCassette(_)));
+        assert!(matches!(
+            err(b"ureq-proto cassette 1\n> 5\nab\n"),
+            CassetteError::BadCassette(_)
+        ));
+        assert!(matches!(
+            err(b"ureq-proto cassette 1\n> 2\nab\n"),
+            CassetteError::BadCassette(_)
+        ));
+        assert!(matches!(
+            err(b"ureq-proto cassette 1\n> 18446744073709551615\nab\n"),
+            CassetteError::BadCassette(_)
+        ));
+    }
+}
@@ -9,12 +9,15 @@ use http::{
 };
 
 use crate::body::calculate_max_input;
-use crate::ext::{HeaderIterExt, MethodExt, StatusExt};
-use crate::parser::try_parse_response;
+use crate::ext::{header_tokens, HeaderIterExt, MethodExt, StatusExt};
+use crate::parser::{try_parse_response, try_parse_response_with, try_parse_status};
+use crate::redact::RedactedHeaders;
 use crate::util::ArrayVec;
-use crate::{BodyMode, Error};
+use crate::{BodyMode, ChunkExtension, Error, HeaderCasing, Limits, ParsePolicy};
 
+use super::call::is_interim;
 use super::holder::CallHolder;
+use super::observer::WireObserver;
 
 #[doc(hidden)]
 pub mod state {
@@ -41,6 +44,8 @@ pub mod state {
     flow_state!(RecvResponse);
     flow_state!(RecvBody);
     flow_state!(Redirect);
+    flow_state!(Upgrade);
+    flow_state!(Tunnel);
     flow_state!(Cleanup);
 }
 use self::state::*;
@@ -60,6 +65,19 @@ pub(crate) struct Inner<B> {
     pub await_100_continue: bool,
     pub status: Option<StatusCode>,
     pub location: Option<HeaderValue>,
+    pub upgrade: Option<HeaderValue>,
+    pub tunnel: bool,
+    pub leftover: Vec<u8>,
+    pub via_proxy: bool,
+    pub proxy_authorization: Option<HeaderValue>,
+    pub unconsumed_input: usize,
+    pub policy: ParsePolicy,
+    pub limits: Limits,
+    pub casing: HeaderCasing,
+    pub header_order: Vec<HeaderName>,
+    pub redacted: RedactedHeaders,
+    pub interim: Vec<Response<()>>,
+    pub observer: Option<Box<dyn WireObserver>>,
 }
 
 impl<B> Inner<B> {
@@ -71,6 +89,15 @@ impl<B> Inner<B> {
             None => false,
         }
     }
+
+    fn push_interim(&mut self, response: Response<()>) -> Result<(), Error> {
+        let max = self.limits.max_interim_responses;
+        if self.interim.len() >= max {
+            return Err(Error::TooManyInterimResponses(max));
+        }
+        self.interim.push(response);
+        Ok(())
+    }
 }
 
 /// Reasons for an ended flow that requires the connection to be closed.
@@ -101,6 +128,12 @@ pub enum CloseReason {
     /// We do not know how much body data to receive. The socket will be closed
     /// when it's done. This is HTTP/1.0 semantics.
     CloseDelimitedBody,
+
+    /// A successful CONNECT turned the connection into a tunnel.
+    ///
+    /// The connection is handed over to whatever runs in the tunnel and can't
+    /// be reused for further requests.
+    Tunnel,
 }
 
 impl CloseReason {
@@ -111,15 +144,20 @@ impl CloseReason {
             CloseReason::ServerConnectionClose => "server sent Connection: close",
             CloseReason::Not100Continue => "got non-100 response before sending body",
             CloseReason::CloseDelimitedBody => "response body is close delimited",
+            CloseReason::Tunnel => "connection is a CONNECT tunnel",
         }
     }
 }
 
 impl<B, S> Flow<B, S> {
-    fn wrap(inner: Inner<B>) -> Flow<B, S>
+    fn wrap(mut inner: Inner<B>) -> Flow<B, S>
     where
         S: Named,
     {
+        if let Some(observer) = &mut inner.observer {
+            observer.state(S::name());
+        }
+
         let wrapped = Flow {
             inner,
             _ph: PhantomData,
@@ -138,6 +176,27 @@ impl<B, S> Flow<B, S> {
         &mut self.inner.call
     }
 
+    /// Start over with the original request.
+    ///
+    /// Headers added in the `Prepare` state are lost, apart from the proxy settings,
+    /// limits, header casing and order, and redacted headers.
+    pub(crate) fn into_prepare(mut self) -> Result<Flow<B, Prepare>, Error> {
+        let request = self.inner.call.request_mut().take_request();
+
+        let observer = self.inner.observer.take();
+        let mut next = Flow::with_observer(request, self.inner.policy, observer)?;
+        next.set_limits(self.inner.limits);
+        next.set_header_casing(self.inner.casing);
+        next.set_header_order(self.inner.header_order);
+        next.set_redacted(self.inner.redacted);
+
+        if self.inner.via_proxy {
+            next.send_via_proxy(self.inner.proxy_authorization)?;
+        }
+
+        Ok(next)
+    }
+
     #[cfg(test)]
     pub(crate) fn inner(&self) -> &Inner<B> {
         &self.inner
@@ -148,7 +207,19 @@ impl<B, S> Flow<B, S> {
 
 impl<B> Flow<B, Prepare> {
     /// Create a new Flow.
-    pub fn new(request: Request<B>) -> Result<Self, Error> {
+    ///
+    /// The `policy` decides how strictly the response is parsed. Use
+    /// `ParsePolicy::default()` for the lenient handling of common server bugs.
+    pub fn new(request: Request<B>, policy: ParsePolicy) -> Result<Self, Error> {
+        Self::with_observer(request, policy, None)
+    }
+
+    /// Like `new()`, but with the observer in place before entering `Prepare`.
+    fn with_observer(
+        request: Request<B>,
+        policy: ParsePolicy,
+        observer: Option<Box<dyn WireObserver>>,
+    ) -> Result<Self, Error> {
         let mut close_reason = ArrayVec::from_fn(|_| CloseReason::Http10);
 
         if request.version() == Version::HTTP_10 {
@@ -163,7 +234,7 @@ impl<B> Flow<B, Prepare> {
         let should_send_body = request.method().need_request_body();
         let await_100_continue = request.headers().iter().has_expect_100();
 
-        let call = CallHolder::new(request)?;
+        let call = CallHolder::new(request, policy)?;
 
         let inner = Inner {
             call,
@@ -172,6 +243,19 @@ impl<B> Flow<B, Prepare> {
             await_100_continue,
             status: None,
             location: None,
+            upgrade: None,
+            tunnel: false,
+            leftover: Vec::new(),
+            via_proxy: false,
+            proxy_authorization: None,
+            unconsumed_input: 0,
+            policy,
+            limits: Limits::new(),
+            casing: HeaderCasing::default(),
+            header_order: Vec::new(),
+            redacted: RedactedHeaders::default(),
+            interim: Vec::new(),
+            observer,
         };
 
         Ok(Flow::wrap(inner))
@@ -208,6 +292,95 @@ impl<B> Flow<B, Prepare> {
         self.call_mut().request_mut().set_header(key, value)
     }
 
+    /// Send the request via a forward proxy.
+    ///
+    /// The request target is sent in absolute-form, i.e. `GET http://host/path HTTP/1.1`,
+    /// and `proxy_authorization` is sent as the `proxy-authorization` header. For `CONNECT`
+    /// the target remains `host:port`.
+    ///
+    /// This is meant for plain `http` requests. For `https`, the connection to the proxy
+    /// should be made into a tunnel using `CONNECT`, and the request sent as usual
+    /// inside the tunnel.
+    ///
+    /// The proxy settings are kept when following redirects to `http` uris, and dropped
+    /// for redirects to `https`.
+    pub fn send_via_proxy(
+        &mut self,
+        proxy_authorization: Option<HeaderValue>,
+    ) -> Result<(), Error> {
+        let is_connect = self.method() == Method::CONNECT;
+
+        if !is_connect {
+            let uri = self.uri();
+
+            if uri.scheme().is_none() || uri.host().is_none() {
+                return Err(Error::ProxyRequiresAbsoluteUri);
+            }
+
+            self.call_mut().request_mut().set_absolute_form();
+        }
+
+        if let Some(v) = &proxy_authorization {
+            self.header("proxy-authorization", v.clone())?;
+        }
+
+        self.inner.via_proxy = true;
+        self.inner.proxy_authorization = proxy_authorization;
+
+        Ok(())
+    }
+
+    /// Set limits on the received response headers.
+    ///
+    /// The limits are kept when following redirects.
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.inner.limits = limits;
+        self.inner.call.set_limits(limits);
+    }
+
+    /// Set how header names are written on the wire.
+    ///
+    /// This applies to all headers, including `host`, `content-length` and
+    /// `transfer-encoding` added by the flow. It is kept when following redirects.
+    pub fn set_header_casing(&mut self, casing: HeaderCasing) {
+        self.inner.casing = casing.clone();
+        self.inner.call.set_header_casing(casing);
+    }
+
+    /// Set the order of the headers on the wire.
+    ///
+    /// Headers with these names are written first, in the given order. The remaining
+    /// headers follow in the default order. This includes headers added by the flow,
+    /// i.e. `vec![HOST]` puts the `host` header first. It is kept when following redirects.
+    pub fn set_header_order(&mut self, order: Vec<HeaderName>) {
+        self.inner.header_order = order.clone();
+        self.call_mut().request_mut().set_header_order(order);
+    }
+
+    /// Set the headers with values masked in the trace logging.
+    ///
+    /// With `trace` level enabled for this crate, all bytes sent and received are logged.
+    /// The values of these headers are replaced by `*` before anything reaches the `log`
+    /// crate, including folded continuation lines. Names are matched case insensitively.
+    ///
+    /// Defaults to `authorization`, `proxy-authorization`, `cookie` and `set-cookie`. It is
+    /// kept when following redirects.
+    pub fn set_redacted_headers(&mut self, names: Vec<HeaderName>) {
+        self.set_redacted(RedactedHeaders::new(names));
+    }
+
+    fn set_redacted(&mut self, redacted: RedactedHeaders) {
+        self.inner.redacted = redacted.clone();
+        self.inner.call.set_redacted(redacted);
+    }
+
+    /// Set an observer of the data written and read.
+    ///
+    /// The observer is kept when following redirects.
+    pub fn set_observer(&mut self, observer: Box<dyn WireObserver>) {
+        self.inner.observer = Some(observer);
+    }
+
     /// Convert the call to send body despite method.
     ///
     /// Methods like GET, HEAD and DELETE should not have a request body.
@@ -252,11 +425,33 @@ impl<B> Flow<B, SendRequest> {
     ///
     /// The `Ok(usize)` is the number of bytes of the `output` buffer that was used.
     pub fn write(&mut self, output: &mut [u8]) -> Result<usize, Error> {
-        match &mut self.inner.call {
-            CallHolder::WithoutBody(v) => v.write(output),
-            CallHolder::WithBody(v) => v.write(&[], output).map(|r| r.1),
+        let before = self.call().prelude_progress();
+
+        let output_used = match &mut self.inner.call {
+            CallHolder::WithoutBody(v) => v.write(output)?,
+            CallHolder::WithBody(v) => v.write(&[], output)?.1,
             _ => unreachable!(),
+        };
+
+        if let Some(observer) = &mut self.inner.observer {
+            let after = self.inner.call.prelude_progress();
+            let request = self.inner.call.request();
+
+            if !before.0 && after.0 {
+                observer.request_start(request.uri());
+
+                let (method, target, version) = request.prelude();
+                observer.request_line(method, &target, version);
+            }
+
+            for (name, value) in request.headers().skip(before.1).take(after.1 - before.1) {
+                observer.request_header(name, value);
+            }
+
+            observer.sent(&output[..output_used]);
         }
+
+        Ok(output_used)
     }
 
     /// The configured method.
@@ -286,6 +481,96 @@ impl<B> Flow<B, SendRequest> {
         Ok(map)
     }
 
+    /// Render the request as an equivalent `curl` command line.
+    ///
+    /// The method, uri, HTTP version and headers are as they would be sent, including the
+    /// `host`, `content-length` and `transfer-encoding` added by the flow, and the
+    /// [`HeaderCasing`]. Each argument is quoted for a POSIX shell.
+    ///
+    /// The body is not known to the flow. For a request with a body, the command has
+    /// `--data-binary @-`, which reads the body from stdin. For a request
+    /// [sent via a proxy](Flow::send_via_proxy), the command has `--proxy "$http_proxy"`,
+    /// since the flow does not know the proxy address either.
+    ///
+    /// With `redact`, the values of the headers set with
+    /// [`Flow<Prepare>::set_redacted_headers()`](Flow::set_redacted_headers) are masked,
+    /// like in the trace logging.
+    ///
+    /// ```
+    /// # use ureq_proto::client::flow::Flow;
+    /// # use ureq_proto::http::Request;
+    /// # use ureq_proto::ParsePolicy;
+    /// let request = Request::put("http://q.test/it's")
+    ///     .header("authorization", "secret")
+    ///     .body(())
+    ///     .unwrap();
+    ///
+    /// let mut flow = Flow::new(request, ParsePolicy::default()).unwrap().proceed();
+    ///
+    /// assert_eq!(
+    ///     flow.curl_command(true).unwrap(),
+    ///     "curl --http1.1 -X PUT -H 'host: q.test' -H 'transfer-encoding: chunked' \
+    ///     -H 'authorization: ******' --data-binary @- 'http://q.test/it'\\''s'"
+    /// );
+    /// ```
+    pub fn curl_command(&mut self, redact: bool) -> Result<String, Error> {
+        self.call_mut().analyze_request()?;
+
+        let request = self.call().request();
+        let mut args = vec!["curl".to_string()];
+
+        args.push(match request.version() {
+            Version::HTTP_10 => "--http1.0".to_string(),
+            _ => "--http1.1".to_string(),
+        });
+
+        match *request.method() {
+            Method::GET => {}
+            // -X HEAD makes curl wait for a body.
+            Method::HEAD => args.push("--head".to_string()),
+            ref method => {
+                args.push("-X".to_string());
+                args.push(shell_quote(method.as_str()));
+            }
+        }
+
+        for (name, value) in request.headers() {
+            let mut line = Vec::new();
+            // Writing to a Vec can't fail.
+            let _ = self.inner.casing.write_name(&mut line, name);
+            line.extend_from_slice(b": ");
+            line.extend_from_slice(value.as_bytes());
+
+            let line = if redact {
+                self.inner.redacted.redact(&line).into_owned()
+            } else {
+                line
+            };
+
+            args.push("-H".to_string());
+            args.push(shell_quote(&String::from_utf8_lossy(&line)));
+        }
+
+        if self.inner.should_send_body {
+            args.push("--data-binary".to_string());
+            args.push("@-".to_string());
+        }
+
+        if self.inner.via_proxy {
+            // The flow does not know where the proxy is.
+            args.push("--proxy".to_string());
+            args.push("\"$http_proxy\"".to_string());
+        }
+
+        args.push(shell_quote(&request.uri().to_string()));
+
+        Ok(args.join(" "))
+    }
+
+    pub(crate) fn has_request_body(&self) -> bool {
+        self.inner.should_send_body
+    }
+
     /// Check whether the entire request has been sent.
     ///
     /// This is useful when the output buffer is small and we need to repeatedly
@@ -365,14 +650,28 @@ impl<B> Flow<B, Await100> {
     /// The results are:
     ///
     /// * `Ok(0)` - not enough data yet, continue waiting (or `proceed()` if you think we waited enough)
-    /// * `Ok(n)` - `n` number of input bytes were consumed. Call `proceed()` next
+    /// * `Ok(n)` - `n` number of input bytes were consumed. Call `proceed()` next, unless
+    ///   [`Flow<Await100>::can_keep_await_100()`](Flow::can_keep_await_100) is still `true`
     /// * `Err(e)` - some error that is not recoverable
+    ///
+    /// Other informational (1xx) responses, such as `103 Early Hints`, are consumed while
+    /// still waiting for the 100-continue, and are available via
+    /// [`Flow<Await100>::take_interim_responses()`](Flow::take_interim_responses).
     pub fn try_read_100(&mut self, input: &[u8]) -> Result<usize, Error> {
+        // Such as 103 Early Hints, which may have headers.
+        if let Some(status) = try_parse_status(input) {
+            if status != StatusCode::CONTINUE && is_interim(status) {
+                return self.try_read_interim(input);
+            }
+        }
+
         // Try parsing a status line without any headers. The line we are looking for is:
         //
         //   HTTP/1.1 100 Continue\r\n\r\n
         //
-        // There should be no headers.
+        // There should be no headers. Obs-fold is never accepted here, regardless of
+        // self.inner.policy, since it makes httparse wait for more input on the first header
+        // line instead of telling us there are too many headers.
         match try_parse_response::<0>(input) {
             Ok(v) => match v {
                 Some((input_used, response)) => {
@@ -381,6 +680,12 @@ impl<B> Flow<B, Await100> {
                     if response.status() == StatusCode::CONTINUE {
                         // should_send_body ought to be true since initialization.
                         assert!(self.inner.should_send_body);
+
+                        if let Some(observer) = &mut self.inner.observer {
+                            observer.received(&input[..input_used]);
+                            observer.response_head(&response);
+                        }
+
                         Ok(input_used)
                     } else {
                         // We encountered a status line, without headers, but it wasn't 100,
@@ -416,6 +721,41 @@ impl<B> Flow<B, Await100> {
         }
     }
 
+    fn try_read_interim(&mut self, input: &[u8]) -> Result<usize, Error> {
+        let max_headers = self.inner.limits.max_headers;
+        let mut headers = vec![httparse::EMPTY_HEADER; max_headers];
+
+        let parsed = try_parse_response_with(input, &self.inner.policy, &mut headers).map_err(
+            |e| match e {
+                Error::HttpParseTooManyHeaders => Error::TooManyHeaders(max_headers),
+                e => e,
+            },
+        )?;
+
+        let Some((input_used, response)) = parsed else {
+            // Not enough input yet.
+            return Ok(0);
+        };
+
+        if let Some(observer) = &mut self.inner.observer {
+            observer.received(&input[..input_used]);
+            observer.response_head(&response);
+        }
+
+        // Keep waiting for the 100-continue.
+        self.inner.push_interim(response)?;
+
+        Ok(input_used)
+    }
+
+    /// Take the interim (1xx) responses received while waiting for 100-continue.
+    ///
+    /// Responses not taken here are still available in the `RecvResponse` state. At most
+    /// [`Limits::max_interim_responses()`] are held until taken.
+    pub fn take_interim_responses(&mut self) -> Vec<Response<()>> {
+        std::mem::take(&mut self.inner.interim)
+    }
+
     /// Tell if there is any point in waiting for more data from the server.
     ///
     /// Becomes `false` as soon as `try_read_100()` got enough data to determine what to do next.
@@ -470,7 +810,44 @@ impl<B> Flow<B, SendBody> {
     /// To indicate that the body is fully sent, you call write with an `input` parameter set to `&[]`.
     /// This ends the `transfer-encoding: chunked` and ensures the state is correct to proceed.
     pub fn write(&mut self, input: &[u8], output: &mut [u8]) -> Result<(usize, usize), Error> {
-        self.inner.call.as_with_body_mut().write(input, output)
+        let (input_used, output_used) = self.inner.call.as_with_body_mut().write(input, output)?;
+
+        if let Some(observer) = &mut self.inner.observer {
+            if input_used > 0 {
+                observer.body_out(&input[..input_used]);
+            }
+            observer.sent(&output[..output_used]);
+        }
+
+        Ok((input_used, output_used))
+    }
+
+    /// End a chunked request body with trailer fields.
+    ///
+    /// Trailers are useful for values only known once the entire body is sent, such as
+    /// a checksum. This is used instead of calling `write()` with an empty input to end
+    /// the body. Only possible when the body is `transfer-encoding: chunked`.
+    ///
+    /// Every field in `trailers` must be announced up front in a `trailer` request header,
+    /// or this errors with [`Error::TrailerNotAnnounced`].
+    ///
+    /// The ending is written in one go. The result is how much of the `output` that was used.
+    pub fn write_trailers(
+        &mut self,
+        trailers: &HeaderMap,
+        output: &mut [u8],
+    ) -> Result<usize, Error> {
+        let output_used = self
+            .inner
+            .call
+            .as_with_body_mut()
+            .write_trailers(trailers, output)?;
+
+        if let Some(observer) = &mut self.inner.observer {
+            observer.sent(&output[..output_used]);
+        }
+
+        Ok(output_used)
     }
 
     /// Helper to avoid copying memory.
@@ -550,6 +927,11 @@ impl<B> Flow<B, RecvResponse> {
     /// Try reading a response from the input.
     ///
     /// This requires the entire response, including all headers to be present in the input buffer.
+    /// Until then, call it again with the same buffer and more bytes appended. Only the appended
+    /// bytes are scanned for the end of the headers. A shorter `input` starts the scan over.
+    ///
+    /// This is a breaking change from earlier versions, which re-parsed the entire `input` on
+    /// every call. See [`Call::try_response()`](crate::client::call::Call::try_response).
     ///
     /// The `(usize, Option<Response()>)` is `(input amount consumed, response`).
     ///
@@ -557,15 +939,32 @@ impl<B> Flow<B, RecvResponse> {
     /// a `Some(Response)`. This can happen if the server returned a 100-continue, and due to
     /// timing reasons we did not receive it while we were in the `Await100` flow state. This
     /// "spurios" 100 will be discarded before we parse the actual response.
+    ///
+    /// A `101 Switching Protocols` response is checked against the `upgrade` request header.
+    /// Since the bytes following a 101 belong to the new protocol, the entire `input` is
+    /// consumed, and any bytes after the response are available via
+    /// [`Flow<Upgrade>::leftover()`](Flow::leftover).
+    ///
+    /// Likewise, a 2xx response to `CONNECT` consumes the entire `input`, and the bytes
+    /// after the response are available via [`Flow<Tunnel>::leftover()`](Flow::leftover).
+    ///
+    /// Other informational (1xx) responses, such as `103 Early Hints`, are consumed without
+    /// returning `Some(Response)`, and are available via
+    /// [`Flow<RecvResponse>::take_interim_responses()`](Flow::take_interim_responses).
     pub fn try_response(&mut self, input: &[u8]) -> Result<(usize, Option<Response<()>>), Error> {
         let maybe_response = self.inner.call.as_recv_response_mut().try_response(input)?;
 
-        let (input_used, response) = match maybe_response {
+        let (mut input_used, response) = match maybe_response {
             Some(v) => v,
             // Not enough input for a full response yet
             None => return Ok((0, None)),
         };
 
+        if let Some(observer) = &mut self.inner.observer {
+            observer.received(&input[..input_used]);
+            observer.response_head(&response);
+        }
+
         if response.status() == StatusCode::CONTINUE && self.inner.await_100_continue {
             // We have received a "delayed" 100-continue. This means the server did
             // not produce the 100-continue response in time while we were in the
@@ -576,6 +975,35 @@ impl<B> Flow<B, RecvResponse> {
             return Ok((input_used, None));
         }
 
+        if response.status() != StatusCode::CONTINUE && is_interim(response.status()) {
+            // Such as 103 Early Hints. Keep it for the caller and wait for the final response.
+            self.inner.push_interim(response)?;
+            return Ok((input_used, None));
+        }
+
+        if response.status() == StatusCode::SWITCHING_PROTOCOLS {
+            let request = self.inner.call.request();
+            let protocol = check_upgrade(request.headers(), response.headers())?;
+            self.inner.upgrade = Some(protocol.clone());
+
+            // Whatever follows the response is the new protocol.
+            self.inner.leftover = input[input_used..].to_vec();
+            input_used = input.len();
+        }
+
+        let is_connect = self.inner.call.request().method() == Method::CONNECT;
+
+        if is_connect && response.status().is_success() {
+            self.inner.tunnel = true;
+            self.inner.close_reason.push(CloseReason::Tunnel);
+
+            // Whatever follows the response belongs to the tunnel.
+            self.inner.leftover = input[input_used..].to_vec();
+            input_used = input.len();
+        }
+
+        self.inner.unconsumed_input = input.len() - input_used;
+
         self.inner.status = Some(response.status());
         // We want the last Location header.
         self.inner.location = response
@@ -594,6 +1022,16 @@ impl<B> Flow<B, RecvResponse> {
         Ok((input_used, Some(response)))
     }
 
+    /// Take the interim (1xx) responses received before the final response.
+    ///
+    /// These are informational, such as `102 Processing` or `103 Early Hints` with
+    /// `link` headers for resources to preload. The flow keeps waiting for the final
+    /// response regardless of whether they are taken, but at most
+    /// [`Limits::max_interim_responses()`] are held until taken.
+    pub fn take_interim_responses(&mut self) -> Vec<Response<()>> {
+        std::mem::take(&mut self.inner.interim)
+    }
+
     /// Tell if we have finished receiving the response.
     pub fn can_proceed(&self) -> bool {
         self.inner.call.as_recv_response().is_finished()
@@ -629,7 +1067,11 @@ impl<B> Flow<B, RecvResponse> {
         } else {
             self.inner.call = CallHolder::RecvBody(call_body);
 
-            Some(if self.inner.is_redirect() {
+            Some(if self.inner.upgrade.is_some() {
+                RecvResponseResult::Upgrade(Flow::wrap(self.inner))
+            } else if self.inner.tunnel {
+                RecvResponseResult::Tunnel(Flow::wrap(self.inner))
+            } else if self.inner.is_redirect() {
                 RecvResponseResult::Redirect(Flow::wrap(self.inner))
             } else {
                 RecvResponseResult::Cleanup(Flow::wrap(self.inner))
@@ -648,10 +1090,52 @@ pub enum RecvResponseResult<B> {
     /// Follow a redirect.
     Redirect(Flow<B, Redirect>),
 
+    /// Server accepted a protocol upgrade (`101 Switching Protocols`).
+    Upgrade(Flow<B, Upgrade>),
+
+    /// Proxy accepted a `CONNECT` and the connection is now a tunnel.
+    Tunnel(Flow<B, Tunnel>),
+
     /// Run cleanup.
     Cleanup(Flow<B, Cleanup>),
 }
 
+/// Check a 101 response against the requested upgrade, returning the new protocol.
+fn check_upgrade<'a, 'b>(
+    request_headers: impl Iterator<Item = (&'a HeaderName, &'a HeaderValue)>,
+    response_headers: &'b HeaderMap,
+) -> Result<&'b HeaderValue, Error> {
+    let requested: Vec<&str> = header_tokens(request_headers, "upgrade").collect();
+
+    if requested.is_empty() {
+        return Err(Error::UpgradeNotRequested);
+    }
+
+    if !response_headers.iter().has_token("connection", "upgrade") {
+        return Err(Error::BadUpgradeResponse(
+            "missing connection: upgrade".to_string(),
+        ));
+    }
+
+    let protocol = response_headers
+        .get("upgrade")
+        .ok_or_else(|| Error::BadUpgradeResponse("missing upgrade header".to_string()))?;
+
+    let is_requested = protocol
+        .to_str()
+        .map(|p| requested.iter().any(|r| r.eq_ignore_ascii_case(p.trim())))
+        .unwrap_or(false);
+
+    if !is_requested {
+        return Err(Error::BadUpgradeResponse(format!(
+            "protocol not requested: {}",
+            String::from_utf8_lossy(protocol.as_bytes())
+        )));
+    }
+
+    Ok(protocol)
+}
+
 // //////////////////////////////////////////////////////////////////////////////////////////// RECV BODY
 
 impl<B> Flow<B, RecvBody> {
@@ -662,7 +1146,33 @@ impl<B> Flow<B, RecvBody> {
     ///
     /// The result `(usize, usize)` is `(input consumed, output buffer used)`.
     pub fn read(&mut self, input: &[u8], output: &mut [u8]) -> Result<(usize, usize), Error> {
-        self.inner.call.as_recv_body_mut().read(input, output)
+        let was_ended = self.inner.call.as_recv_body().is_ended();
+
+        let (input_used, output_used) = self.inner.call.as_recv_body_mut().read(input, output)?;
+
+        // Reading after the end consumes nothing, and must not change what was left over
+        // when the body ended.
+        if !was_ended {
+            self.inner.unconsumed_input = input.len() - input_used;
+        }
+
+        if let Some(observer) = &mut self.inner.observer {
+            observer.received(&input[..input_used]);
+            if output_used > 0 {
+                observer.body_in(&output[..output_used]);
+            }
+        }
+
+        Ok((input_used, output_used))
+    }
+
+    /// Signal that the transport hit EOF, i.e. the server closed the connection.
+    ///
+    /// For a close delimited body, this is the end of the body, and we can proceed. For
+    /// a body delimited by `content-length` or chunked, that isn't fully read, the body is
+    /// truncated and this returns [`Error::IncompleteBody`].
+    pub fn signal_eof(&mut self) -> Result<(), Error> {
+        self.inner.call.as_recv_body_mut().signal_eof()
     }
 
     /// Set if we are stopping on chunk boundaries.
@@ -687,11 +1197,34 @@ impl<B> Flow<B, RecvBody> {
         self.inner.call.as_recv_body().is_on_chunk_boundary()
     }
 
+    /// Extension parameters of the chunk being read.
+    ///
+    /// With `transfer-encoding: chunked`, each chunk can carry `;name=value` parameters after
+    /// the chunk size. Combined with `stop_on_chunk_boundary(true)`, the body can be read
+    /// chunk-by-chunk, and this gives the extensions of the chunk that was just read (or is
+    /// partially read).
+    ///
+    /// Empty unless the body is chunked and the chunk has extensions.
+    pub fn chunk_extensions(&self) -> &[ChunkExtension] {
+        self.inner.call.as_recv_body().chunk_extensions()
+    }
+
     /// Tell which kind of mode the response body is.
     pub fn body_mode(&self) -> BodyMode {
         self.call().body_mode()
     }
 
+    /// Trailer fields sent after the response body.
+    ///
+    /// Trailers are only possible for `transfer-encoding: chunked`, where they follow
+    /// the last chunk. Returns `None` until the body is fully read, or if the body is
+    /// not chunked. A chunked body without trailers gives an empty `HeaderMap`.
+    ///
+    /// The trailers are not available after `proceed()`.
+    pub fn trailers(&self) -> Option<&HeaderMap> {
+        self.inner.call.as_recv_body().trailers()
+    }
+
     /// Check if the response body has been fully received.
     pub fn can_proceed(&self) -> bool {
         let call = self.inner.call.as_recv_body();
@@ -792,8 +1325,17 @@ impl<B> Flow<B, Redirect> {
         let mut request = previous.take_request();
         *request.method_mut() = new_method;
 
+        // Proxy credentials are for the proxy, not the target. Only a plain http
+        // request continues via the proxy. An https request would go via a tunnel.
+        let keep_proxy = self.inner.via_proxy && uri.scheme() == Some(&Scheme::HTTP);
+
         // Next state
-        let mut next = Flow::new(request)?;
+        let observer = self.inner.observer.take();
+        let mut next = Flow::with_observer(request, self.inner.policy, observer)?;
+        next.set_limits(self.inner.limits);
+        next.set_header_casing(self.inner.casing.clone());
+        next.set_header_order(self.inner.header_order.clone());
+        next.set_redacted(self.inner.redacted.clone());
 
         let request = next.inner.call.request_mut();
 
@@ -808,9 +1350,16 @@ impl<B> Flow<B, Redirect> {
         if !keep_auth_header {
             request.unset_header("authorization")?;
         }
+        if !keep_proxy {
+            request.unset_header("proxy-authorization")?;
+        }
         request.unset_header("cookie")?;
         request.unset_header("content-length")?;
 
+        if keep_proxy {
+            next.send_via_proxy(self.inner.proxy_authorization.clone())?;
+        }
+
         // TODO(martin): clear out unwanted headers
 
         Ok(Some(next))
@@ -864,6 +1413,60 @@ pub enum RedirectAuthHeaders {
     SameHost,
 }
 
+// //////////////////////////////////////////////////////////////////////////////////////////// UPGRADE
+
+impl<B> Flow<B, Upgrade> {
+    /// The protocol the server switched to, from the `upgrade` response header.
+    pub fn protocol(&self) -> &HeaderValue {
+        // unwrap is ok because we can't be here without an upgrade.
+        self.inner.upgrade.as_ref().unwrap()
+    }
+
+    /// Bytes received after the `101 Switching Protocols` response.
+    ///
+    /// These were part of the input to `try_response()` but belong to the new protocol.
+    pub fn leftover(&self) -> &[u8] {
+        &self.inner.leftover
+    }
+
+    /// Take the bytes received after the `101 Switching Protocols` response.
+    ///
+    /// The connection is handed over to the new protocol and must not be
+    /// returned to a connection pool.
+    pub fn into_leftover(self) -> Vec<u8> {
+        self.inner.leftover
+    }
+}
+
+// //////////////////////////////////////////////////////////////////////////////////////////// TUNNEL
+
+impl<B> Flow<B, Tunnel> {
+    /// Bytes received after the 2xx response to `CONNECT`.
+    ///
+    /// These were part of the input to `try_response()` but belong to the tunnel.
+    pub fn leftover(&self) -> &[u8] {
+        &self.inner.leftover
+    }
+
+    /// Take the bytes received after the 2xx response to `CONNECT`.
+    pub fn into_leftover(self) -> Vec<u8> {
+        self.inner.leftover
+    }
+
+    /// Tell if we must close the connection.
+    ///
+    /// Always true, since the connection is handed over to the tunnel and must not
+    /// be returned to a connection pool.
+    pub fn must_close_connection(&self) -> bool {
+        true
+    }
+
+    /// The reason the connection can't be reused.
+    pub fn close_reason(&self) -> Option<&'static str> {
+        self.inner.close_reason.first().map(|s| s.explain())
+    }
+}
+
 // //////////////////////////////////////////////////////////////////////////////////////////// CLEANUP
 
 impl<B> Flow<B, Cleanup> {
@@ -876,6 +1479,34 @@ impl<B> Flow<B, Cleanup> {
     pub fn close_reason(&self) -> Option<&'static str> {
         self.inner.close_reason.first().map(|s| s.explain())
     }
+
+    /// Number of bytes at the end of the input that were not consumed.
+    ///
+    /// This is counted from the input to the last call of `try_response()` or `read()`. If
+    /// that input was `N` bytes, the bytes after `N - unconsumed_input()` were not part of
+    /// this response. When pipelining, they belong to the next response, otherwise the
+    /// server sent more than it should.
+    pub fn unconsumed_input(&self) -> usize {
+        self.inner.unconsumed_input
+    }
+
+    /// Tell if the response body was read to the end.
+    ///
+    /// This is only `false` for a close delimited body, which also means
+    /// [`Flow::must_close_connection()`] is `true`.
+    pub fn is_body_fully_read(&self) -> bool {
+        self.inner.call.as_recv_body().is_ended()
+    }
+
+    /// Tell if the connection is in a clean state to be reused.
+    ///
+    /// This means we don't need to close the connection, the body was fully read, and
+    /// there were no excess bytes after the response.
+    pub fn is_clean_for_reuse(&self) -> bool {
+        !self.must_close_connection()
+            && self.is_body_fully_read()
+            && self.inner.unconsumed_input == 0
+    }
 }
 
 // ////////////////////////////////////////////////////////////////////////////////////////////
@@ -885,3 +1516,14 @@ impl<B, State: Named> fmt::Debug for Flow<B, State> {
         write!(f, "Flow<{}>", State::name())
     }
 }
+
+/// Quote an argument for a POSIX shell, unless it is made of only safe characters.
+fn shell_quote(arg: &str) -> String {
+    let is_safe = |c: char| c.is_ascii_alphanumeric() || "-_./:=@".contains(c);
+
+    if !arg.is_empty() && arg.chars().all(is_safe) {
+        return arg.to_string();
+    }
+
+    format!("'{}'", arg.replace('\'', "'\\''"))
+}
@@ -0,0 +1,513 @@
+//! Recording of flows in the HTTP Archive (HAR) format.
+
+use std::fmt::Write;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use http::{header, HeaderName, HeaderValue, Method, Response, Uri, Version};
+
+use crate::parser::ReasonPhrase;
+use crate::util::base64;
+
+use super::call::is_interim;
+use super::observer::WireObserver;
+
+type Clock = Box<dyn FnMut() -> Duration + Send>;
+
+/// Recorder of flows as [HAR 1.2](http://www.softwareishard.com/blog/har-12-spec/).
+///
+/// The recorder is a [`WireObserver`] that is set on a flow in the `Prepare` state.
+/// Each request becomes one entry, which includes each hop when following redirects,
+/// since the observer is kept by [`Flow<Redirect>::as_new_flow()`][as_new_flow].
+/// Clones share the same recording, which means one clone can be given to the flow
+/// while another is kept to produce the HAR.
+///
+/// This crate does no I/O and has no notion of time. The `clock` returns the time
+/// since the Unix epoch and is read on every state transition. The time spent in
+/// `SendRequest` and `SendBody` is the `send` timing, `Await100` and `RecvResponse`
+/// is `wait`, and `RecvBody` is `receive`. The entry `time` is the sum of these, which
+/// means the time spent in `Redirect` and `Prepare` between hops is not included.
+///
+/// ```
+/// use std::time::SystemTime;
+/// use ureq_proto::client::flow::Flow;
+/// use ureq_proto::client::har::HarRecorder;
+/// use ureq_proto::http::Request;
+/// use ureq_proto::ParsePolicy;
+///
+/// let recorder = HarRecorder::new(|| {
+///     SystemTime::now()
+///         .duration_since(SystemTime::UNIX_EPOCH)
+///         .unwrap()
+/// });
+///
+/// let request = Request::get("http://example.test").body(()).unwrap();
+/// let mut flow = Flow::new(request, ParsePolicy::default()).unwrap();
+/// flow.set_observer(Box::new(recorder.clone()));
+///
+/// // drive the flow to Cleanup
+///
+/// let json = recorder.to_json();
+/// assert!(json.starts_with(r#"{"log":{"version":"1.2""#));
+/// ```
+///
+/// [as_new_flow]: super::flow::Flow::as_new_flow
+#[derive(Clone)]
+pub struct HarRecorder {
+    shared: Arc<Mutex<Recording>>,
+}
+
+struct Recording {
+    clock: Clock,
+    // The current state and when it was entered.
+    state: Option<(&'static str, Duration)>,
+    entries: Vec<Entry>,
+}
+
+struct Entry {
+    started: Duration,
+    method: String,
+    url: String,
+    version: Version,
+    request_headers: Vec<(String, String)>,
+    request_headers_size: usize,
+    body_out: Vec<u8>,
+    response: Option<RecordedResponse>,
+    body_in: Vec<u8>,
+    send: Duration,
+    wait: Duration,
+    receive: Duration,
+}
+
+struct RecordedResponse {
+    status: u16,
+    reason: String,
+    version: Version,
+    headers: Vec<(String, String)>,
+    headers_size: usize,
+}
+
+impl HarRecorder {
+    /// Create a recorder with a clock returning the time since the Unix epoch.
+    pub fn new<F>(clock: F) -> Self
+    where
+        F: FnMut() -> Duration + Send + 'static,
+    {
+        HarRecorder {
+            shared: Arc::new(Mutex::new(Recording {
+                clock: Box::new(clock),
+                state: None,
+                entries: Vec::new(),
+            })),
+        }
+    }
+
+    /// Number of recorded entries.
+    pub fn len(&self) -> usize {
+        self.lock().entries.len()
+    }
+
+    /// Tells if there are no recorded entries.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The recording as a HAR JSON document.
+    ///
+    /// Entries without a response yet are left out.
+    pub fn to_json(&self) -> String {
+        let recording = self.lock();
+
+        let mut out = String::new();
+
+        out.push_str(r#"{"log":{"version":"1.2","creator":{"name":"#);
+        json_str(&mut out, env!("CARGO_PKG_NAME"));
+        out.push_str(r#","version":"#);
+        json_str(&mut out, env!("CARGO_PKG_VERSION"));
+        out.push_str(r#"},"entries":["#);
+
+        let complete = recording.entries.iter().filter(|e| e.response.is_some());
+
+        for (i, entry) in complete.enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            entry.write_json(&mut out);
+        }
+
+        out.push_str("]}}");
+
+        out
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Recording> {
+        // A poisoned lock still holds a valid recording.
+        self.shared.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn with_entry(&self, f: impl FnOnce(&mut Entry)) {
+        if let Some(entry) = self.lock().entries.last_mut() {
+            f(entry);
+        }
+    }
+}
+
+impl WireObserver for HarRecorder {
+    fn request_start(&mut self, uri: &Uri) {
+        let mut recording = self.lock();
+
+        let started = match recording.state {
+            Some((_, since)) => since,
+            None => (recording.clock)(),
+        };
+
+        recording.entries.push(Entry {
+            started,
+            method: String::new(),
+            url: uri.to_string(),
+            version: Version::HTTP_11,
+            request_headers: Vec::new(),
+            request_headers_size: 0,
+            body_out: Vec::new(),
+            response: None,
+            body_in: Vec::new(),
+            send: Duration::ZERO,
+            wait: Duration::ZERO,
+            receive: Duration::ZERO,
+        });
+    }
+
+    fn request_line(&mut self, method: &Method, target: &str, version: Version) {
+        self.with_entry(|e| {
+            e.method = method.to_string();
+            e.version = version;
+            // The request line, i.e. "GET / HTTP/1.1\r\n", and the final "\r\n".
+            e.request_headers_size = method.as_str().len() + target.len() + 14;
+        });
+    }
+
+    fn request_header(&mut self, name: &HeaderName, value: &HeaderValue) {
+        self.with_entry(|e| {
+            e.request_headers_size += name.as_str().len() + value.len() + 4;
+            e.request_headers.push(header_pair(name, value));
+        });
+    }
+
+    fn body_out(&mut self, data: &[u8]) {
+        self.with_entry(|e| e.body_out.extend_from_slice(data));
+    }
+
+    fn response_head(&mut self, response: &Response<()>) {
+        let status = response.status();
+
+        // HAR has no place for interim responses.
+        if is_interim(status) {
+            return;
+        }
+
+        let reason = match response.extensions().get::<ReasonPhrase>() {
+            Some(v) => v.as_str(),
+            None => status.canonical_reason().unwrap_or(""),
+        };
+
+        let headers: Vec<_> = response
+            .headers()
+            .iter()
+            .map(|(n, v)| header_pair(n, v))
+            .collect();
+
+        // The status line, i.e. "HTTP/1.1 200 OK\r\n", and the final "\r\n".
+        let headers_size = 17
+            + reason.len()
+            + headers
+                .iter()
+                .map(|(n, v)| n.len() + v.len() + 4)
+                .sum::<usize>();
+
+        let response = RecordedResponse {
+            status: status.as_u16(),
+            reason: reason.to_string(),
+            version: response.version(),
+            headers,
+            headers_size,
+        };
+
+        self.with_entry(|e| e.response = Some(response));
+    }
+
+    fn body_in(&mut self, data: &[u8]) {
+        self.with_entry(|e| e.body_in.extend_from_slice(data));
+    }
+
+    fn state(&mut self, state: &'static str) {
+        let mut recording = self.lock();
+        let now = (recording.clock)();
+
+        if let Some((previous, since)) = recording.state {
+            let elapsed = now.saturating_sub(since);
+
+            if let Some(entry) = recording.entries.last_mut() {
+                match previous {
+                    "SendRequest" | "SendBody" => entry.send += elapsed,
+                    "Await100" | "RecvResponse" => entry.wait += elapsed,
+                    "RecvBody" => entry.receive += elapsed,
+                    _ => {}
+                }
+            }
+        }
+
+        recording.state = Some((state, now));
+    }
+}
+
+impl Entry {
+    fn write_json(&self, out: &mut String) {
+        // Only called for entries with a response.
+        let response = self.response.as_ref().unwrap();
+
+        let time = self.send + self.wait + self.receive;
+
+        out.push_str(r#"{"startedDateTime":"#);
+        json_str(out, &iso_8601(self.started));
+        let _ = write!(out, r#","time":{},"request":{{"method":"#, millis(time));
+        json_str(out, &self.method);
+        out.push_str(r#","url":"#);
+        json_str(out, &self.url);
+        out.push_str(r#","httpVersion":"#);
+        json_str(out, &format!("{:?}", self.version));
+        out.push_str(r#","cookies":"#);
+        write_cookies(out, &self.request_headers, header::COOKIE);
+        out.push_str(r#","headers":"#);
+        write_headers(out, &self.request_headers);
+        out.push_str(r#","queryString":["#);
+        write_query(out, &self.url);
+        out.push(']');
+
+        if !self.body_out.is_empty() {
+            out.push_str(r#","postData":{"mimeType":"#);
+            json_str(out, content_type(&self.request_headers));
+            out.push_str(r#","text":"#);
+            json_str(out, &String::from_utf8_lossy(&self.body_out));
+            out.push('}');
+        }
+
+        let _ = write!(
+            out,
+            r#","headersSize":{},"bodySize":{}}},"response":{{"status":{},"statusText":"#,
+            self.request_headers_size,
+            self.body_out.len(),
+            response.status,
+        );
+        json_str(out, &response.reason);
+        out.push_str(r#","httpVersion":"#);
+        json_str(out, &format!("{:?}", response.version));
+        out.push_str(r#","cookies":"#);
+        write_cookies(out, &response.headers, header::SET_COOKIE);
+        out.push_str(r#","headers":"#);
+        write_headers(out, &response.headers);
+
+        let _ = write!(
+            out,
+            r#","content":{{"size":{},"mimeType":"#,
+            self.body_in.len()
+        );
+        json_str(out, content_type(&response.headers));
+        if !self.body_in.is_empty() {
+            out.push_str(r#","text":"#);
+            match std::str::from_utf8(&self.body_in) {
+                Ok(v) => json_str(out, v),
+                Err(_) => {
+                    json_str(out, &base64(&self.body_in));
+                    out.push_str(r#","encoding":"base64""#);
+                }
+            }
+        }
+
+        out.push_str(r#"},"redirectURL":"#);
+        json_str(
+            out,
+            header_value(&response.headers, header::LOCATION).unwrap_or(""),
+        );
+
+        let _ = write!(
+            out,
+            r#","headersSize":{},"bodySize":{}}},"cache":{{}},"timings":{{"send":{},"wait":{},"receive":{}}}}}"#,
+            response.headers_size,
+            self.body_in.len(),
+            millis(self.send),
+            millis(self.wait),
+            millis(self.receive),
+        );
+    }
+}
+
+fn header_pair(name: &HeaderName, value: &HeaderValue) -> (String, String) {
+    let value = String::from_utf8_lossy(value.as_bytes()).to_string();
+    (name.to_string(), value)
+}
+
+fn header_value(headers: &[(String, String)], name: HeaderName) -> Option<&str> {
+    headers
+        .iter()
+        .find(|(n, _)| *n == name.as_str())
+        .map(|(_, v)| v.as_str())
+}
+
+fn content_type(headers: &[(String, String)]) -> &str {
+    header_value(headers, header::CONTENT_TYPE).unwrap_or("")
+}
+
+fn write_headers(out: &mut String, headers: &[(String, String)]) {
+    out.push('[');
+    for (i, (name, value)) in headers.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        write_pair(out, name, value);
+    }
+    out.push(']');
+}
+
+/// Cookies from `cookie` (`a=1; b=2`) or `set-cookie` (`a=1; Path=/`) headers.
+fn write_cookies(out: &mut String, headers: &[(String, String)], name: HeaderName) {
+    let is_set_cookie = name == header::SET_COOKIE;
+
+    let cookies = headers
+        .iter()
+        .filter(|(n, _)| *n == name.as_str())
+        .flat_map(|(_, v)| {
+            let pairs = v.split(';');
+            // Only the first pair of set-cookie is the cookie, the rest are attributes.
+            pairs.take(if is_set_cookie { 1 } else { usize::MAX })
+        })
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(n, v)| (n.trim(), v.trim()));
+
+    out.push('[');
+    for (i, (name, value)) in cookies.enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        write_pair(out, name, value);
+    }
+    out.push(']');
+}
+
+fn write_query(out: &mut String, url: &str) {
+    let query = match url.parse::<Uri>() {
+        Ok(uri) => uri.query().map(|q| q.to_string()),
+        Err(_) => None,
+    };
+
+    let query = match query {
+        Some(v) => v,
+        None => return,
+    };
+
+    for (i, (name, value)) in url::form_urlencoded::parse(query.as_bytes()).enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        write_pair(out, &name, &value);
+    }
+}
+
+fn write_pair(out: &mut String, name: &str, value: &str) {
+    out.push_str(r#"{"name":"#);
+    json_str(out, name);
+    out.push_str(r#","value":"#);
+    json_str(out, value);
+    out.push('}');
+}
+
+fn json_str(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// HAR timings are in milliseconds.
+fn millis(d: Duration) -> f64 {
+    d.as_micros() as f64 / 1000.0
+}
+
+/// Format the time since the Unix epoch as `2024-01-02T03:04:05.678Z`.
+fn iso_8601(since_epoch: Duration) -> String {
+    let secs = since_epoch.as_secs();
+    let days = (secs / 86_400) as i64;
+    let rem = secs % 86_400;
+
+    // Days to civil date, from http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60,
+        since_epoch.subsec_millis()
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_iso_8601() {
+        assert_eq!(iso_8601(Duration::ZERO), "1970-01-01T00:00:00.000Z");
+        assert_eq!(
+            iso_8601(Duration::from_millis(1_709_251_199_123)),
+            "2024-02-29T23:59:59.123Z"
+        );
+    }
+
+    #[test]
+    fn test_json_str() {
+        let mut out = String::new();
+        json_str(&mut out, "a\"b\\c\n\u{1}");
+        assert_eq!(out, r#""a\"b\\c\n\u0001""#);
+    }
+
+    #[test]
+    fn test_cookies() {
+        let headers = vec![
+            ("cookie".to_string(), "a=1; b=2".to_string()),
+            ("set-cookie".to_string(), "c=3; Path=/".to_string()),
+        ];
+
+        let mut out = String::new();
+        write_cookies(&mut out, &headers, header::COOKIE);
+        assert_eq!(
+            out,
+            r#"[{"name":"a","value":"1"},{"name":"b","value":"2"}]"#
+        );
+
+        let mut out = String::new();
+        write_cookies(&mut out, &headers, header::SET_COOKIE);
+        assert_eq!(out, r#"[{"name":"c","value":"3"}]"#);
+    }
+}
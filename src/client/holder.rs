@@ -3,7 +3,8 @@ use std::mem;
 use http::Request;
 
 use crate::ext::MethodExt;
-use crate::{BodyMode, Error};
+use crate::redact::RedactedHeaders;
+use crate::{BodyMode, Error, HeaderCasing, Limits, ParsePolicy};
 
 use super::amended::AmendedRequest;
 use super::call::state::{RecvBody, RecvResponse, WithBody, WithoutBody};
@@ -22,14 +23,48 @@ pub(crate) enum CallHolder<B> {
 }
 
 impl<B> CallHolder<B> {
-    pub fn new(request: Request<B>) -> Result<Self, Error> {
+    pub fn new(request: Request<B>, policy: ParsePolicy) -> Result<Self, Error> {
         Ok(if request.method().need_request_body() {
-            CallHolder::WithBody(Call::with_body(request)?)
+            let mut call = Call::with_body(request)?;
+            call.set_parse_policy(policy);
+            CallHolder::WithBody(call)
         } else {
-            CallHolder::WithoutBody(Call::without_body(request)?)
+            let mut call = Call::without_body(request)?;
+            call.set_parse_policy(policy);
+            CallHolder::WithoutBody(call)
         })
     }
 
+    pub fn set_limits(&mut self, limits: Limits) {
+        match self {
+            CallHolder::WithoutBody(v) => v.set_limits(limits),
+            CallHolder::WithBody(v) => v.set_limits(limits),
+            CallHolder::RecvResponse(v) => v.set_limits(limits),
+            CallHolder::RecvBody(v) => v.set_limits(limits),
+            CallHolder::Empty => unreachable!(),
+        }
+    }
+
+    pub fn set_header_casing(&mut self, casing: HeaderCasing) {
+        match self {
+            CallHolder::WithoutBody(v) => v.set_header_casing(casing),
+            CallHolder::WithBody(v) => v.set_header_casing(casing),
+            CallHolder::RecvResponse(v) => v.set_header_casing(casing),
+            CallHolder::RecvBody(v) => v.set_header_casing(casing),
+            CallHolder::Empty => unreachable!(),
+        }
+    }
+
+    pub fn set_redacted(&mut self, redacted: RedactedHeaders) {
+        match self {
+            CallHolder::WithoutBody(v) => v.set_redacted(redacted),
+            CallHolder::WithBody(v) => v.set_redacted(redacted),
+            CallHolder::RecvResponse(v) => v.set_redacted(redacted),
+            CallHolder::RecvBody(v) => v.set_redacted(redacted),
+            CallHolder::Empty => unreachable!(),
+        }
+    }
+
     pub fn request(&self) -> &AmendedRequest<B> {
         match self {
             CallHolder::WithoutBody(v) => v.amended(),
@@ -102,6 +137,16 @@ impl<B> CallHolder<B> {
         }
     }
 
+    pub(crate) fn prelude_progress(&self) -> (bool, usize) {
+        match self {
+            CallHolder::WithoutBody(v) => v.prelude_progress(),
+            CallHolder::WithBody(v) => v.prelude_progress(),
+            CallHolder::RecvResponse(v) => v.prelude_progress(),
+            CallHolder::RecvBody(v) => v.prelude_progress(),
+            CallHolder::Empty => unreachable!(),
+        }
+    }
+
     pub(crate) fn body_mode(&self) -> BodyMode {
         match self {
             CallHolder::WithoutBody(v) => v.body_mode(),
@@ -21,6 +21,10 @@
 //!   version and the response headers
 //! * **RecvBody** - Receive the response body
 //! * **Redirect** - Handle redirects, potentially spawning new requests
+//! * **Upgrade** - The server accepted a protocol upgrade (101 Switching
+//!   Protocols) and the connection now speaks the new protocol
+//! * **Tunnel** - A proxy accepted a `CONNECT` and the connection is now
+//!   a tunnel to the target
 //! * **Cleanup** - Return the connection to the pool or close it
 //!
 //!
@@ -41,13 +45,13 @@
 //! │                       │            │                       │
 //!                         │            ▼                       │
 //! │                       └─▶┌──────────────────┐◀─────────────┘
-//!              ┌─────────────│   RecvResponse   │──┐
-//! │            │             └──────────────────┘  │
-//!              │                       │           │
-//! │            ▼                       ▼           │
-//!    ┌──────────────────┐    ┌──────────────────┐  │
-//! └ ─│     Redirect     │◀───│     RecvBody     │  │
-//!    └──────────────────┘    └──────────────────┘  │
+//!              ┌─────────────│   RecvResponse   │──┬──────────────┬───────────────────────┐
+//! │            │             └──────────────────┘  │              │                       │
+//!              │                       │           │              │                       │
+//! │            ▼                       ▼           │              ▼                       ▼
+//!    ┌──────────────────┐    ┌──────────────────┐  │    ┌──────────────────┐    ┌──────────────────┐
+//! └ ─│     Redirect     │◀───│     RecvBody     │  │    │     Upgrade      │    │      Tunnel      │
+//!    └──────────────────┘    └──────────────────┘  │    └──────────────────┘    └──────────────────┘
 //!              │                       │           │
 //!              │                       ▼           │
 //!              │             ┌──────────────────┐  │
@@ -60,6 +64,7 @@
 //! ```
 //! use ureq_proto::client::flow::*;
 //! use ureq_proto::http::Request;
+//! use ureq_proto::ParsePolicy;
 //!
 //! let request = Request::put("https://example.test/my-path")
 //!     .header("Expect", "100-continue")
@@ -69,7 +74,7 @@
 //!
 //! // ********************************** Prepare
 //!
-//! let mut flow = Flow::new(request).unwrap();
+//! let mut flow = Flow::new(request, ParsePolicy::default()).unwrap();
 //!
 //! // Prepare with state from cookie jar. The uri
 //! // is used to key the cookies.
@@ -243,8 +248,16 @@
 
 pub mod call;
 
+pub mod cassette;
+
 pub mod flow;
 
+pub mod har;
+
+pub mod observer;
+
+pub mod pipeline;
+
 mod amended;
 
 mod holder;
@@ -255,5 +268,5 @@ mod test;
 /// Max number of additional headers to amend an HTTP request with
 pub const MAX_EXTRA_HEADERS: usize = 64;
 
-/// Max number of headers to parse from an HTTP response
+/// Default max number of headers to parse from an HTTP response, see [`Limits`](crate::Limits)
 pub const MAX_RESPONSE_HEADERS: usize = 128;
@@ -0,0 +1,93 @@
+//! Structured callbacks for what a flow sends and receives.
+
+use std::fmt;
+
+use http::{HeaderName, HeaderValue, Method, Response, Uri, Version};
+
+/// Observer of the data a [`Flow`](super::flow::Flow) writes and reads.
+///
+/// Set in the `Prepare` state via
+/// [`Flow::set_observer()`](super::flow::Flow::set_observer). It is kept when following
+/// redirects. All methods have a default no-op implementation, so an observer only
+/// implements the ones it is interested in. It must be `Send + Sync`, like the flow
+/// holding it.
+///
+/// Unlike the trace logging, the observer gets the data as is. Any redaction of
+/// sensitive headers is up to the observer.
+///
+/// ```
+/// use ureq_proto::client::observer::WireObserver;
+/// use ureq_proto::http::{HeaderName, HeaderValue};
+///
+/// #[derive(Default)]
+/// struct HeaderCounter(usize);
+///
+/// impl WireObserver for HeaderCounter {
+///     fn request_header(&mut self, _name: &HeaderName, _value: &HeaderValue) {
+///         self.0 += 1;
+///     }
+/// }
+/// ```
+pub trait WireObserver: Send + Sync {
+    /// A request to `uri` is about to be written.
+    ///
+    /// Called once per request, before the request line. Following a redirect
+    /// starts a new request.
+    fn request_start(&mut self, uri: &Uri) {
+        let _ = uri;
+    }
+
+    /// The request line was written.
+    fn request_line(&mut self, method: &Method, target: &str, version: Version) {
+        let _ = (method, target, version);
+    }
+
+    /// A request header was written.
+    fn request_header(&mut self, name: &HeaderName, value: &HeaderValue) {
+        let _ = (name, value);
+    }
+
+    /// Request body bytes were written, before any chunked encoding.
+    ///
+    /// Data sent directly using `consume_direct_write()` is not seen by the flow and
+    /// not reported.
+    fn body_out(&mut self, data: &[u8]) {
+        let _ = data;
+    }
+
+    /// A response head was parsed. This includes any 1xx responses.
+    fn response_head(&mut self, response: &Response<()>) {
+        let _ = response;
+    }
+
+    /// Response body bytes were read, after any chunked decoding.
+    fn body_in(&mut self, data: &[u8]) {
+        let _ = data;
+    }
+
+    /// Bytes were written to the output, exactly as they are to be sent.
+    ///
+    /// This is the request line, headers and the body after any chunked encoding.
+    fn sent(&mut self, data: &[u8]) {
+        let _ = data;
+    }
+
+    /// Bytes were consumed from the input, exactly as they were received.
+    ///
+    /// Data following a `101 Switching Protocols` or successful `CONNECT` response
+    /// belongs to another protocol and is not reported.
+    fn received(&mut self, data: &[u8]) {
+        let _ = data;
+    }
+
+    /// The flow entered a new state, i.e. `"RecvResponse"`.
+    fn state(&mut self, state: &'static str) {
+        let _ = state;
+    }
+}
+
+impl fmt::Debug for dyn WireObserver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "WireObserver")
+    }
+}
@@ -0,0 +1,215 @@
+//! Pipelining of several requests over one connection.
+//!
+//! With HTTP/1.1 pipelining, requests are sent back to back without waiting for
+//! the responses, and the server answers them in the same order. Only idempotent
+//! requests without a body can be pipelined (RFC 9112 9.3.2).
+//!
+//! ```
+//! use ureq_proto::client::flow::*;
+//! use ureq_proto::client::pipeline::Pipeline;
+//! use ureq_proto::http::Request;
+//! use ureq_proto::ParsePolicy;
+//!
+//! let mut pipeline = Pipeline::new();
+//!
+//! for path in ["/a", "/b"] {
+//!     let uri = format!("http://example.test{}", path);
+//!     let request = Request::get(uri).body(()).unwrap();
+//!     let flow = Flow::new(request, ParsePolicy::default()).unwrap().proceed();
+//!     pipeline.push(flow).unwrap();
+//! }
+//!
+//! // Both requests are written to the same output.
+//! let mut output = vec![0_u8; 1024];
+//! let output_used = pipeline.write(&mut output).unwrap();
+//!
+//! assert_eq!(&output[..output_used], b"\
+//!     GET /a HTTP/1.1\r\nhost: example.test\r\n\r\n\
+//!     GET /b HTTP/1.1\r\nhost: example.test\r\n\r\n");
+//!
+//! // Responses arrive in the order of the requests.
+//! let input = b"\
+//!     HTTP/1.1 200 OK\r\ncontent-length: 1\r\n\r\na\
+//!     HTTP/1.1 200 OK\r\ncontent-length: 1\r\n\r\nb";
+//!
+//! let mut input = &input[..];
+//!
+//! while let Some(mut flow) = pipeline.next_response() {
+//!     let (input_used, _response) = flow.try_response(input).unwrap();
+//!     input = &input[input_used..];
+//!
+//!     let mut flow = match flow.proceed() {
+//!         Some(RecvResponseResult::RecvBody(v)) => v,
+//!         _ => panic!(),
+//!     };
+//!
+//!     // Reading stops at the end of the body. The rest of the
+//!     // input belongs to the next response.
+//!     let (input_used, _) = flow.read(input, &mut output).unwrap();
+//!     input = &input[input_used..];
+//!
+//!     let flow = match flow.proceed() {
+//!         Some(RecvBodyResult::Cleanup(v)) => v,
+//!         _ => panic!(),
+//!     };
+//!
+//!     if !pipeline.finish(&flow) {
+//!         // Send the remaining requests on a new connection.
+//!         let _retry = pipeline.abort().unwrap();
+//!         break;
+//!     }
+//! }
+//!
+//! assert!(input.is_empty());
+//! ```
+
+use std::collections::VecDeque;
+
+use crate::Error;
+
+use super::flow::state::{Cleanup, Prepare, RecvResponse, SendRequest};
+use super::flow::{Flow, SendRequestResult};
+
+/// Several requests pipelined over one connection.
+///
+/// Requests are written in the order they are pushed, and the responses are received
+/// in the same order via [`Pipeline::next_response()`]. If the connection can't be reused
+/// after a response, such as `connection: close` or a close-delimited body, the remaining
+/// requests are aborted and must be sent again on a new connection.
+pub struct Pipeline<B> {
+    to_send: VecDeque<Flow<B, SendRequest>>,
+    in_flight: VecDeque<Flow<B, RecvResponse>>,
+    aborted: bool,
+}
+
+impl<B> Pipeline<B> {
+    /// Create a new empty pipeline.
+    pub fn new() -> Self {
+        Pipeline {
+            to_send: VecDeque::new(),
+            in_flight: VecDeque::new(),
+            aborted: false,
+        }
+    }
+
+    /// Queue a request to be sent.
+    ///
+    /// The request method must be idempotent, and the request must not have a body.
+    pub fn push(&mut self, flow: Flow<B, SendRequest>) -> Result<(), Error> {
+        if self.aborted {
+            return Err(Error::PipelineAborted);
+        }
+
+        if !flow.method().is_idempotent() || flow.has_request_body() {
+            return Err(Error::NotPipelinable(flow.method().clone()));
+        }
+
+        self.to_send.push_back(flow);
+
+        Ok(())
+    }
+
+    /// Write queued requests back to back to the buffer.
+    ///
+    /// Writes as many requests as fit in the output. It can be called repeatedly in
+    /// situations where the output buffer is small.
+    ///
+    /// The `Ok(usize)` is the number of bytes of the `output` buffer that was used.
+    pub fn write(&mut self, output: &mut [u8]) -> Result<usize, Error> {
+        if self.aborted {
+            return Err(Error::PipelineAborted);
+        }
+
+        let mut output_used = 0;
+
+        while let Some(flow) = self.to_send.front_mut() {
+            if !flow.can_proceed() {
+                match flow.write(&mut output[output_used..]) {
+                    Ok(n) => output_used += n,
+                    // Previous requests filled the output.
+                    Err(Error::OutputOverflow) if output_used > 0 => break,
+                    Err(e) => return Err(e),
+                }
+
+                if !flow.can_proceed() {
+                    break;
+                }
+            }
+
+            // unwrap is ok because we just looked at the front.
+            let flow = self.to_send.pop_front().unwrap();
+
+            match flow.proceed()? {
+                Some(SendRequestResult::RecvResponse(v)) => self.in_flight.push_back(v),
+                _ => unreachable!("pipelined request without body to proceed to RecvResponse"),
+            }
+        }
+
+        Ok(output_used)
+    }
+
+    /// Tell if all queued requests have been written.
+    pub fn is_all_sent(&self) -> bool {
+        self.to_send.is_empty()
+    }
+
+    /// Number of requests waiting for a response, including those not yet sent.
+    pub fn len(&self) -> usize {
+        self.to_send.len() + self.in_flight.len()
+    }
+
+    /// Tell if there are no requests waiting for a response.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Take the flow to receive the next response.
+    ///
+    /// Returns `None` if there is no sent request waiting for a response, or if the
+    /// pipeline is aborted.
+    pub fn next_response(&mut self) -> Option<Flow<B, RecvResponse>> {
+        if self.aborted {
+            return None;
+        }
+
+        self.in_flight.pop_front()
+    }
+
+    /// Report a response that has been fully received.
+    ///
+    /// Returns `true` if the connection can be used for the remaining requests. When
+    /// `false`, the pipeline is aborted, and [`Pipeline::abort()`] gives the requests
+    /// to send again on a new connection.
+    pub fn finish(&mut self, flow: &Flow<B, Cleanup>) -> bool {
+        if flow.must_close_connection() {
+            self.aborted = true;
+        }
+
+        !self.aborted
+    }
+
+    /// Tell if the pipeline is aborted.
+    pub fn is_aborted(&self) -> bool {
+        self.aborted
+    }
+
+    /// Abort the pipeline and give back the requests without a response.
+    ///
+    /// This is also used when the connection is handed over after an upgrade
+    /// or a `CONNECT` tunnel. The returned flows are in the order of the requests.
+    /// Any headers added in the `Prepare` state, such as cookies, must be added again.
+    pub fn abort(&mut self) -> Result<Vec<Flow<B, Prepare>>, Error> {
+        self.aborted = true;
+
+        let in_flight = self.in_flight.drain(..).map(Flow::into_prepare);
+        let to_send = self.to_send.drain(..).map(Flow::into_prepare);
+
+        in_flight.chain(to_send).collect()
+    }
+}
+
+impl<B> Default for Pipeline<B> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,181 @@
+use http::Request;
+
+use crate::client::cassette::{Cassette, CassetteError, CassetteRecorder, ReplayEnd, Replayer};
+use crate::client::flow::state::Prepare;
+use crate::client::flow::{
+    Flow, RecvBodyResult, RecvResponseResult, RedirectAuthHeaders, SendRequestResult,
+};
+use crate::ParsePolicy;
+
+use super::scenario::Scenario;
+
+fn post_flow(x: &str) -> Flow<(), Prepare> {
+    let request = Request::post("http://q.test/a")
+        .header("content-length", "5")
+        .header("x", x)
+        .body(())
+        .unwrap();
+
+    Flow::new(request, ParsePolicy::default()).unwrap()
+}
+
+const REDIRECT: &[u8] = b"HTTP/1.1 302 Found\r\nlocation: /b\r\ncontent-length: 0\r\n\r\n";
+const CHUNKED: &[u8] = b"HTTP/1.1 200 OK\r\ntransfer-encoding: chunked\r\n\r\n2\r\nhi\r\n0\r\n\r\n";
+
+fn record() -> Cassette {
+    let recorder = CassetteRecorder::new();
+
+    let mut flow = post_flow("1");
+    flow.set_observer(Box::new(recorder.clone()));
+
+    let mut output = vec![0; 1024];
+
+    let mut flow = flow.proceed();
+    flow.write(&mut output).unwrap();
+    let SendRequestResult::SendBody(mut flow) = flow.proceed().unwrap().unwrap() else {
+        unreachable!();
+    };
+    flow.write(b"hello", &mut output).unwrap();
+
+    let mut flow = flow.proceed().unwrap();
+    flow.try_response(REDIRECT).unwrap();
+    let RecvResponseResult::Redirect(mut flow) = flow.proceed().unwrap() else {
+        unreachable!();
+    };
+
+    let flow = flow
+        .as_new_flow(RedirectAuthHeaders::Never)
+        .unwrap()
+        .unwrap();
+
+    let mut flow = flow.proceed();
+    flow.write(&mut output).unwrap();
+    let SendRequestResult::RecvResponse(mut flow) = flow.proceed().unwrap().unwrap() else {
+        unreachable!();
+    };
+
+    let (n, _) = flow.try_response(CHUNKED).unwrap();
+    let RecvResponseResult::RecvBody(mut flow) = flow.proceed().unwrap() else {
+        unreachable!();
+    };
+    flow.read(&CHUNKED[n..], &mut output).unwrap();
+    let RecvBodyResult::Cleanup(_) = flow.proceed().unwrap() else {
+        unreachable!();
+    };
+
+    recorder.cassette()
+}
+
+#[test]
+fn record_interactions() {
+    let cassette = record();
+
+    let interactions = cassette.interactions();
+    assert_eq!(interactions.len(), 2);
+
+    assert_eq!(
+        interactions[0].sent(),
+        b"POST /a HTTP/1.1\r\nhost: q.test\r\ncontent-length: 5\r\nx: 1\r\n\r\nhello"
+    );
+    assert_eq!(interactions[0].received(), REDIRECT);
+
+    assert_eq!(
+        interactions[1].sent(),
+        b"GET /b HTTP/1.1\r\nhost: q.test\r\nx: 1\r\n\r\n"
+    );
+    assert_eq!(interactions[1].received(), CHUNKED);
+}
+
+#[test]
+fn replay_interactions() {
+    let bytes = record().to_bytes();
+    let mut replayer = Replayer::new(Cassette::from_bytes(&bytes).unwrap());
+
+    let replayed = replayer.replay(post_flow("1"), b"hello").unwrap();
+    assert_eq!(replayed.response().status(), 302);
+
+    let ReplayEnd::Redirect(mut flow) = replayed.into_next() else {
+        unreachable!();
+    };
+    let flow = flow
+        .as_new_flow(RedirectAuthHeaders::Never)
+        .unwrap()
+        .unwrap();
+
+    let replayed = replayer.replay(flow, &[]).unwrap();
+    assert_eq!(replayed.response().status(), 200);
+    assert_eq!(replayed.body(), b"hi");
+    assert!(matches!(replayed.into_next(), ReplayEnd::Cleanup(_)));
+
+    assert!(replayer.is_done());
+    let err = replayer.replay(post_flow("1"), b"hello").err().unwrap();
+    assert_eq!(err, CassetteError::Ended);
+}
+
+#[test]
+fn replay_mismatch() {
+    let mut replayer = Replayer::new(record());
+
+    // The request line and host header are 32 bytes, then "content-length: 5\r\nx: ".
+    let err = replayer.replay(post_flow("2"), b"hello").err().unwrap();
+    assert_eq!(err, CassetteError::Mismatch(54));
+
+    let mut replayer = Replayer::new(record());
+    let err = replayer.replay(post_flow("1"), b"hellO").err().unwrap();
+    assert_eq!(err, CassetteError::Mismatch(63));
+}
+
+#[test]
+fn replay_chunked_body_written_differently() {
+    let recorder = CassetteRecorder::new();
+
+    let scenario = Scenario::builder()
+        .post("http://q.test/a")
+        .send_body("hello", true)
+        .observer(recorder.clone())
+        .build();
+
+    let mut flow = scenario.to_send_body();
+    let mut output = vec![0; 1024];
+    flow.write(b"hel", &mut output).unwrap();
+    flow.write(b"lo", &mut output).unwrap();
+    flow.write(&[], &mut output).unwrap();
+
+    let mut flow = flow.proceed().unwrap();
+    let (n, _) = flow.try_response(CHUNKED).unwrap();
+    let RecvResponseResult::RecvBody(mut flow) = flow.proceed().unwrap() else {
+        unreachable!();
+    };
+    flow.read(&CHUNKED[n..], &mut output).unwrap();
+
+    let cassette = recorder.cassette();
+    assert!(cassette.interactions()[0]
+        .sent()
+        .ends_with(b"3\r\nhel\r\n2\r\nlo\r\n0\r\n\r\n"));
+
+    let scenario = Scenario::builder()
+        .post("http://q.test/a")
+        .send_body("hello", true)
+        .build();
+
+    // The recorded chunks differ, but the decoded body is the same.
+    let mut replayer = Replayer::new(cassette.clone());
+    let replayed = replayer.replay(scenario.to_prepare(), b"hello").unwrap();
+    assert_eq!(replayed.body(), b"hi");
+
+    // The offset of a chunked body mismatch is into the decoded body.
+    let mut replayer = Replayer::new(cassette.clone());
+    let err = replayer
+        .replay(scenario.to_prepare(), b"help")
+        .err()
+        .unwrap();
+    let head_len = cassette.interactions()[0].sent().len() - 20;
+    assert_eq!(err, CassetteError::Mismatch(head_len + 3));
+
+    let mut replayer = Replayer::new(cassette);
+    let err = replayer
+        .replay(scenario.to_prepare(), b"hello!")
+        .err()
+        .unwrap();
+    assert_eq!(err, CassetteError::Mismatch(head_len + 5));
+}
@@ -0,0 +1,87 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::client::flow::{
+    RecvBodyResult, RecvResponseResult, RedirectAuthHeaders, SendRequestResult,
+};
+use crate::client::har::HarRecorder;
+use crate::client::test::scenario::Scenario;
+
+#[test]
+fn record_redirect() {
+    // 2024-01-01T00:00:00.000Z
+    let now = Arc::new(AtomicU64::new(1_704_067_200_000));
+
+    let clock = now.clone();
+    let recorder = HarRecorder::new(move || Duration::from_millis(clock.load(Ordering::SeqCst)));
+    let tick = |ms: u64| now.fetch_add(ms, Ordering::SeqCst);
+
+    let scenario = Scenario::builder()
+        .get("http://a.test/x?q=a%20b")
+        .header("cookie", "c=1")
+        .observer(recorder.clone())
+        .build();
+
+    let mut flow = scenario.to_recv_response();
+    let mut output = vec![0; 1024];
+
+    tick(10);
+    let input = b"HTTP/1.1 302 Moved\r\nlocation: /y\r\ncontent-length: 0\r\n\r\n";
+    flow.try_response(input).unwrap();
+    let RecvResponseResult::Redirect(mut flow) = flow.proceed().unwrap() else {
+        unreachable!();
+    };
+
+    let flow = flow
+        .as_new_flow(RedirectAuthHeaders::Never)
+        .unwrap()
+        .unwrap();
+
+    // Between hops, not part of either entry.
+    tick(5);
+    let mut flow = flow.proceed();
+
+    tick(2);
+    flow.write(&mut output).unwrap();
+    let SendRequestResult::RecvResponse(mut flow) = flow.proceed().unwrap().unwrap() else {
+        unreachable!();
+    };
+
+    tick(20);
+    let input = b"HTTP/1.1 200 OK\r\ncontent-type: text/plain\r\n\
+        content-length: 2\r\nset-cookie: s=2; Path=/\r\n\r\nhi";
+    let (n, _) = flow.try_response(input).unwrap();
+    let RecvResponseResult::RecvBody(mut flow) = flow.proceed().unwrap() else {
+        unreachable!();
+    };
+
+    tick(3);
+    flow.read(&input[n..], &mut output).unwrap();
+    let RecvBodyResult::Cleanup(_) = flow.proceed().unwrap() else {
+        unreachable!();
+    };
+
+    assert_eq!(recorder.len(), 2);
+    let json = recorder.to_json();
+
+    let expected = [
+        r#"{"log":{"version":"1.2","creator":{"name":"ureq-proto","#,
+        r#""entries":[{"startedDateTime":"2024-01-01T00:00:00.000Z","time":10,"#,
+        r#""url":"http://a.test/x?q=a%20b","#,
+        r#""cookies":[{"name":"c","value":"1"}],"#,
+        r#""queryString":[{"name":"q","value":"a b"}],"headersSize":54,"bodySize":0}"#,
+        r#""status":302,"statusText":"Moved","#,
+        r#""redirectURL":"/y","headersSize":55,"#,
+        r#""timings":{"send":0,"wait":10,"receive":0}"#,
+        r#"{"startedDateTime":"2024-01-01T00:00:00.015Z","time":25,"#,
+        r#""url":"http://a.test/y","#,
+        r#""cookies":[{"name":"s","value":"2"}],"#,
+        r#""content":{"size":2,"mimeType":"text/plain","text":"hi"}"#,
+        r#""timings":{"send":2,"wait":20,"receive":3}}]}}"#,
+    ];
+
+    for e in expected {
+        assert!(json.contains(e), "{} not in {}", e, json);
+    }
+}
@@ -14,8 +14,20 @@ mod state_recv_body;
 
 mod state_redirect;
 
+mod state_upgrade;
+
+mod state_tunnel;
+
 mod state_cleanup;
 
+mod pipeline;
+
+mod observer;
+
+mod har;
+
+mod cassette;
+
 trait TestSliceExt {
     fn as_str(&self) -> &str;
 }
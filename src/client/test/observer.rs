@@ -0,0 +1,138 @@
+use std::sync::{Arc, Mutex};
+
+use http::{HeaderName, HeaderValue, Method, Response, StatusCode, Version};
+
+use crate::client::flow::{RecvBodyResult, RecvResponseResult, RedirectAuthHeaders};
+use crate::client::observer::WireObserver;
+use crate::client::test::scenario::Scenario;
+
+#[derive(Clone, Default)]
+struct Recorder(Arc<Mutex<Vec<String>>>);
+
+impl Recorder {
+    fn push(&self, event: String) {
+        self.0.lock().unwrap().push(event);
+    }
+
+    fn events(&self) -> Vec<String> {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+impl WireObserver for Recorder {
+    fn request_line(&mut self, method: &Method, target: &str, version: Version) {
+        self.push(format!("line {} {} {:?}", method, target, version));
+    }
+
+    fn request_header(&mut self, name: &HeaderName, value: &HeaderValue) {
+        self.push(format!("header {}: {}", name, value.to_str().unwrap()));
+    }
+
+    fn body_out(&mut self, data: &[u8]) {
+        self.push(format!("out {}", String::from_utf8_lossy(data)));
+    }
+
+    fn response_head(&mut self, response: &Response<()>) {
+        self.push(format!("response {}", response.status().as_u16()));
+    }
+
+    fn body_in(&mut self, data: &[u8]) {
+        self.push(format!("in {}", String::from_utf8_lossy(data)));
+    }
+
+    fn state(&mut self, state: &'static str) {
+        self.push(format!("state {}", state));
+    }
+}
+
+#[test]
+fn observe_post() {
+    let recorder = Recorder::default();
+
+    let scenario = Scenario::builder()
+        .post("http://q.test/path")
+        .send_body("hello", false)
+        .observer(recorder.clone())
+        .build();
+
+    let mut flow = scenario.to_recv_response();
+
+    let input = b"HTTP/1.1 103 Early Hints\r\n\r\n\
+        HTTP/1.1 200 OK\r\ncontent-length: 2\r\n\r\nhi";
+
+    let (n, response) = flow.try_response(input).unwrap();
+    assert!(response.is_none());
+    let (m, response) = flow.try_response(&input[n..]).unwrap();
+    assert!(response.is_some());
+
+    let RecvResponseResult::RecvBody(mut flow) = flow.proceed().unwrap() else {
+        unreachable!();
+    };
+
+    let mut output = vec![0; 32];
+    flow.read(&input[n + m..], &mut output).unwrap();
+
+    let RecvBodyResult::Cleanup(_) = flow.proceed().unwrap() else {
+        unreachable!();
+    };
+
+    assert_eq!(
+        recorder.events(),
+        [
+            "state SendRequest",
+            "line POST /path HTTP/1.1",
+            "header content-length: 5",
+            "header host: q.test",
+            "state SendBody",
+            "out hello",
+            "state RecvResponse",
+            "response 103",
+            "response 200",
+            "state RecvBody",
+            "in hi",
+            "state Cleanup",
+        ]
+    );
+}
+
+#[test]
+fn observe_redirect() {
+    let recorder = Recorder::default();
+
+    let scenario = Scenario::builder()
+        .get("http://q.test/a")
+        .observer(recorder.clone())
+        .redirect(StatusCode::FOUND, "/b")
+        .build();
+
+    let mut flow = scenario.to_redirect();
+
+    let flow = flow
+        .as_new_flow(RedirectAuthHeaders::Never)
+        .unwrap()
+        .unwrap();
+    let _ = flow.proceed();
+
+    let events = recorder.events();
+    assert_eq!(
+        &events[events.len() - 4..],
+        [
+            "response 302",
+            "state Redirect",
+            "state Prepare",
+            "state SendRequest",
+        ]
+    );
+}
+
+#[test]
+fn flow_with_observer_is_send_sync() {
+    fn is_send_sync<T: Send + Sync>(_t: T) {}
+
+    let scenario = Scenario::builder()
+        .get("http://q.test")
+        .observer(Recorder::default())
+        .build();
+
+    is_send_sync(scenario.to_prepare());
+}
@@ -0,0 +1,174 @@
+use http::{Method, Request, Response};
+
+use crate::client::flow::{Flow, RecvBodyResult, RecvResponseResult};
+use crate::client::pipeline::Pipeline;
+use crate::{Error, ParsePolicy};
+
+use super::scenario::{write_response, Scenario};
+use super::TestSliceExt;
+
+fn pipeline(uris: &[&str]) -> Pipeline<()> {
+    let mut pipeline = Pipeline::new();
+
+    for uri in uris {
+        let scenario = Scenario::builder().get(uri).build();
+        pipeline.push(scenario.to_send_request()).unwrap();
+    }
+
+    pipeline
+}
+
+#[test]
+fn reject_not_idempotent() {
+    let mut pipeline = Pipeline::new();
+
+    let scenario = Scenario::builder().post("https://q.test").build();
+    let err = pipeline.push(scenario.to_send_request()).unwrap_err();
+
+    assert_eq!(err, Error::NotPipelinable(Method::POST));
+}
+
+#[test]
+fn reject_body() {
+    let mut pipeline = Pipeline::new();
+
+    let scenario = Scenario::builder()
+        .put("https://q.test")
+        .send_body("hi", false)
+        .build();
+    let err = pipeline.push(scenario.to_send_request()).unwrap_err();
+
+    assert_eq!(err, Error::NotPipelinable(Method::PUT));
+}
+
+#[test]
+fn write_small_output() {
+    let mut pipeline = pipeline(&["https://q.test/a", "https://q.test/b"]);
+
+    // Room for the first request, but not the second.
+    let mut output = vec![0; 40];
+
+    let n = pipeline.write(&mut output).unwrap();
+    assert_eq!(
+        output[..n].as_str(),
+        "GET /a HTTP/1.1\r\nhost: q.test\r\n\r\n"
+    );
+    assert!(!pipeline.is_all_sent());
+
+    let n = pipeline.write(&mut output).unwrap();
+    assert_eq!(
+        output[..n].as_str(),
+        "GET /b HTTP/1.1\r\nhost: q.test\r\n\r\n"
+    );
+    assert!(pipeline.is_all_sent());
+
+    assert_eq!(pipeline.len(), 2);
+}
+
+#[test]
+fn responses_in_order() {
+    let mut pipeline = pipeline(&["https://q.test/a", "https://q.test/b"]);
+
+    pipeline.write(&mut [0; 1024]).unwrap();
+
+    let first = pipeline.next_response().unwrap();
+    let second = pipeline.next_response().unwrap();
+    assert!(pipeline.next_response().is_none());
+    assert!(pipeline.is_empty());
+
+    assert_eq!(first.inner().call.request().uri(), "https://q.test/a");
+    assert_eq!(second.inner().call.request().uri(), "https://q.test/b");
+}
+
+#[test]
+fn abort_on_connection_close() {
+    let mut pipeline = pipeline(&["https://q.test/a", "https://q.test/b", "https://q.test/c"]);
+
+    // Only the first two fit.
+    pipeline.write(&mut [0; 70]).unwrap();
+
+    let mut flow = pipeline.next_response().unwrap();
+
+    let response = Response::builder()
+        .header("connection", "close")
+        .header("content-length", "0")
+        .body(())
+        .unwrap();
+    flow.try_response(&write_response(&response)).unwrap();
+
+    let flow = match flow.proceed() {
+        Some(RecvResponseResult::Cleanup(v)) => v,
+        _ => panic!("Expected Cleanup"),
+    };
+
+    assert!(!pipeline.finish(&flow));
+    assert!(pipeline.is_aborted());
+    assert!(pipeline.next_response().is_none());
+
+    let retry = pipeline.abort().unwrap();
+    let uris: Vec<_> = retry.iter().map(|f| f.uri().to_string()).collect();
+    assert_eq!(uris, ["https://q.test/b", "https://q.test/c"]);
+
+    let err = pipeline.write(&mut [0; 1024]).unwrap_err();
+    assert_eq!(err, Error::PipelineAborted);
+}
+
+#[test]
+fn continue_after_reusable_response() {
+    let mut pipeline = pipeline(&["https://q.test/a", "https://q.test/b"]);
+
+    pipeline.write(&mut [0; 1024]).unwrap();
+
+    let mut flow = pipeline.next_response().unwrap();
+
+    let mut input = write_response(
+        &Response::builder()
+            .header("content-length", "2")
+            .body(())
+            .unwrap(),
+    );
+    input.extend_from_slice(b"hiHTTP/1.1 200 OK\r\n");
+
+    let (n, _) = flow.try_response(&input).unwrap();
+
+    let mut flow = match flow.proceed() {
+        Some(RecvResponseResult::RecvBody(v)) => v,
+        _ => panic!("Expected RecvBody"),
+    };
+
+    let (i, o) = flow.read(&input[n..], &mut [0; 1024]).unwrap();
+    assert_eq!((i, o), (2, 2));
+
+    let flow = match flow.proceed() {
+        Some(RecvBodyResult::Cleanup(v)) => v,
+        _ => panic!("Expected Cleanup"),
+    };
+
+    assert!(pipeline.finish(&flow));
+    assert_eq!(&input[n + i..], b"HTTP/1.1 200 OK\r\n");
+    assert!(pipeline.next_response().is_some());
+}
+
+#[test]
+fn abort_keeps_proxy() {
+    let mut pipeline = Pipeline::new();
+
+    let request = Request::get("http://q.test/a").body(()).unwrap();
+    let mut flow = Flow::new(request, ParsePolicy::default()).unwrap();
+    flow.send_via_proxy(Some("Basic Zm9v".try_into().unwrap()))
+        .unwrap();
+    pipeline.push(flow.proceed()).unwrap();
+
+    let retry = pipeline.abort().unwrap();
+    let mut flow = retry.into_iter().next().unwrap().proceed();
+
+    let mut output = vec![0; 1024];
+    let n = flow.write(&mut output).unwrap();
+
+    assert_eq!(
+        output[..n].as_str(),
+        "GET http://q.test/a HTTP/1.1\r\n\
+        proxy-authorization: Basic Zm9v\r\n\
+        host: q.test\r\n\r\n"
+    );
+}
@@ -1,22 +1,33 @@
 use std::io::Write;
 use std::marker::PhantomData;
+use std::sync::Arc;
 
 use http::{Method, Request, Response, StatusCode};
 
 use crate::client::flow::state::{
-    Await100, Cleanup, Prepare, RecvBody, RecvResponse, Redirect, SendBody, SendRequest,
+    Await100, Cleanup, Prepare, RecvBody, RecvResponse, Redirect, SendBody, SendRequest, Tunnel,
+    Upgrade,
 };
 use crate::client::flow::{Await100Result, Flow, SendRequestResult};
 use crate::client::flow::{RecvBodyResult, RecvResponseResult};
+use crate::client::observer::WireObserver;
+use crate::{Limits, ParsePolicy};
 
 pub struct Scenario {
     request: Request<()>,
     headers_amend: Vec<(String, String)>,
+    proxy: Option<Option<String>>,
+    policy: ParsePolicy,
+    limits: Limits,
+    observer: Option<NewObserver>,
     send_body: Vec<u8>,
     response: Response<()>,
     recv_body: Vec<u8>,
 }
 
+// Each flow made from the scenario gets its own observer.
+type NewObserver = Arc<dyn Fn() -> Box<dyn WireObserver>>;
+
 impl Scenario {
     pub fn builder() -> ScenarioBuilder<()> {
         ScenarioBuilder::new()
@@ -27,12 +38,22 @@ impl Scenario {
     pub fn to_prepare(&self) -> Flow<(), Prepare> {
         // The unwraps here are ok because the user is not supposed to
         // construct tests that test the Scenario builder itself.
-        let mut flow = Flow::new(self.request.clone()).unwrap();
+        let mut flow = Flow::new(self.request.clone(), self.policy).unwrap();
+        flow.set_limits(self.limits);
+
+        if let Some(new_observer) = &self.observer {
+            flow.set_observer(new_observer());
+        }
 
         for (key, value) in &self.headers_amend {
             flow.header(key, value).unwrap();
         }
 
+        if let Some(auth) = &self.proxy {
+            let auth = auth.as_ref().map(|v| v.try_into().unwrap());
+            flow.send_via_proxy(auth).unwrap();
+        }
+
         flow
     }
 
@@ -170,9 +191,38 @@ impl Scenario {
                     _ => unreachable!("Incorrect scenario not leading to_redirect()"),
                 }
             }
+            RecvResponseResult::Upgrade(_) | RecvResponseResult::Tunnel(_) => {
+                unreachable!("Incorrect scenario not leading to_cleanup()")
+            }
             RecvResponseResult::Cleanup(v) => v,
         }
     }
+
+    pub fn to_upgrade(&self) -> Flow<(), Upgrade> {
+        let mut flow = self.to_recv_response();
+
+        let input = write_response(&self.response);
+
+        flow.try_response(&input).unwrap();
+
+        match flow.proceed() {
+            Some(RecvResponseResult::Upgrade(v)) => v,
+            _ => unreachable!("Incorrect scenario not leading to_upgrade()"),
+        }
+    }
+
+    pub fn to_tunnel(&self) -> Flow<(), Tunnel> {
+        let mut flow = self.to_recv_response();
+
+        let input = write_response(&self.response);
+
+        flow.try_response(&input).unwrap();
+
+        match flow.proceed() {
+            Some(RecvResponseResult::Tunnel(v)) => v,
+            _ => unreachable!("Incorrect scenario not leading to_tunnel()"),
+        }
+    }
 }
 
 pub fn write_response(r: &Response<()>) -> Vec<u8> {
@@ -202,6 +252,10 @@ pub fn write_response(r: &Response<()>) -> Vec<u8> {
 pub struct ScenarioBuilder<T> {
     request: Request<()>,
     headers_amend: Vec<(String, String)>,
+    proxy: Option<Option<String>>,
+    policy: ParsePolicy,
+    limits: Limits,
+    observer: Option<NewObserver>,
     send_body: Vec<u8>,
     response: Response<()>,
     recv_body: Vec<u8>,
@@ -221,6 +275,10 @@ impl ScenarioBuilder<()> {
         ScenarioBuilder {
             request,
             headers_amend: self.headers_amend,
+            proxy: None,
+            policy: self.policy,
+            limits: self.limits,
+            observer: None,
             send_body: vec![],
             response: Response::default(),
             recv_body: vec![],
@@ -278,6 +336,26 @@ impl ScenarioBuilder<WithReq> {
         self
     }
 
+    pub fn via_proxy(mut self, proxy_authorization: Option<&str>) -> Self {
+        self.proxy = Some(proxy_authorization.map(|v| v.to_string()));
+        self
+    }
+
+    pub fn policy(mut self, policy: ParsePolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    pub fn limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    pub fn observer<O: WireObserver + Clone + 'static>(mut self, observer: O) -> Self {
+        self.observer = Some(Arc::new(move || Box::new(observer.clone())));
+        self
+    }
+
     pub fn send_body<B: AsRef<[u8]>>(mut self, body: B, chunked: bool) -> Self {
         let body = body.as_ref().to_vec();
         let len = body.len();
@@ -307,6 +385,10 @@ impl ScenarioBuilder<WithReq> {
         let ScenarioBuilder {
             request,
             headers_amend,
+            proxy,
+            policy,
+            limits,
+            observer,
             send_body,
             recv_body,
             ..
@@ -315,6 +397,10 @@ impl ScenarioBuilder<WithReq> {
         ScenarioBuilder {
             request,
             headers_amend,
+            proxy,
+            policy,
+            limits,
+            observer,
             send_body,
             response,
             recv_body,
@@ -327,6 +413,10 @@ impl ScenarioBuilder<WithReq> {
             request: self.request,
             send_body: self.send_body,
             headers_amend: self.headers_amend,
+            proxy: self.proxy,
+            policy: self.policy,
+            limits: self.limits,
+            observer: self.observer,
             response: self.response,
             recv_body: self.recv_body,
         }
@@ -355,6 +445,10 @@ impl ScenarioBuilder<WithRes> {
             request: self.request,
             send_body: self.send_body,
             headers_amend: self.headers_amend,
+            proxy: self.proxy,
+            policy: self.policy,
+            limits: self.limits,
+            observer: self.observer,
             response: self.response,
             recv_body: self.recv_body,
         }
@@ -122,3 +122,41 @@ fn proceed_after_403_with_headers() {
         _ => panic!("proceed after 403 should go to RecvResponse"),
     }
 }
+
+#[test]
+fn early_hints_before_100_continue() {
+    let scenario = Scenario::builder()
+        .put("https://q.test")
+        .header("expect", "100-continue")
+        .build();
+
+    let mut flow = scenario.to_await_100();
+
+    let input = b"HTTP/1.1 103 Early Hints\r\nlink: </a.css>\r\n\r\n";
+
+    // Incomplete headers keep waiting.
+    assert_eq!(flow.try_read_100(&input[..30]).unwrap(), 0);
+    assert!(flow.can_keep_await_100());
+
+    let n = flow.try_read_100(input).unwrap();
+    assert_eq!(n, input.len());
+    assert!(flow.can_keep_await_100());
+
+    let interim = flow.take_interim_responses();
+    assert_eq!(interim.len(), 1);
+    assert_eq!(interim[0].status(), 103);
+    assert_eq!(interim[0].headers().get("link").unwrap(), "</a.css>");
+
+    let n = flow.try_read_100(b"HTTP/1.1 100 Continue\r\n\r\n").unwrap();
+    assert_eq!(n, 25);
+    assert!(!flow.can_keep_await_100());
+
+    let inner = flow.inner();
+    assert!(inner.should_send_body);
+    assert!(inner.close_reason.is_empty());
+
+    match flow.proceed() {
+        Ok(Await100Result::SendBody(_)) => {}
+        _ => panic!("proceed after 103 and 100-continue should go to SendBody"),
+    }
+}
@@ -1,6 +1,6 @@
 use http::{Request, Response, StatusCode, Version};
 
-use crate::client::flow::CloseReason;
+use crate::client::flow::{CloseReason, RecvBodyResult, RecvResponseResult};
 use crate::client::test::scenario::write_response;
 
 use super::scenario::Scenario;
@@ -152,3 +152,112 @@ fn close_due_to_close_delimited_body() {
 
     assert!(flow.must_close_connection());
 }
+
+#[test]
+fn clean_for_reuse() {
+    let scenario = Scenario::builder()
+        .get("https://a.test")
+        .response(Response::new(()))
+        .recv_body("hello", false)
+        .build();
+
+    let flow = scenario.to_cleanup();
+
+    assert!(flow.is_body_fully_read());
+    assert_eq!(flow.unconsumed_input(), 0);
+    assert!(flow.is_clean_for_reuse());
+}
+
+#[test]
+fn not_clean_with_excess_input() {
+    let scenario = Scenario::builder()
+        .get("https://a.test")
+        .response(Response::new(()))
+        .recv_body("hello", false)
+        .build();
+
+    let mut flow = scenario.to_recv_body();
+
+    let (input_used, _) = flow.read(b"helloEXTRA", &mut [0; 1024]).unwrap();
+    assert_eq!(input_used, 5);
+
+    let flow = match flow.proceed() {
+        Some(RecvBodyResult::Cleanup(v)) => v,
+        _ => panic!("Expected Cleanup"),
+    };
+
+    assert!(!flow.must_close_connection());
+    assert!(flow.is_body_fully_read());
+    assert_eq!(flow.unconsumed_input(), 5);
+    assert!(!flow.is_clean_for_reuse());
+}
+
+#[test]
+fn unconsumed_input_from_end_of_body() {
+    let scenario = Scenario::builder()
+        .get("https://a.test")
+        .response(Response::new(()))
+        .recv_body("hello", false)
+        .build();
+
+    let mut flow = scenario.to_recv_body();
+
+    let (input_used, _) = flow.read(b"helloEXTRA", &mut [0; 1024]).unwrap();
+    assert_eq!(input_used, 5);
+
+    // Reading again after the end doesn't change what was left over.
+    let (input_used, _) = flow.read(b"EXTRA AND MORE", &mut [0; 1024]).unwrap();
+    assert_eq!(input_used, 0);
+
+    let flow = match flow.proceed() {
+        Some(RecvBodyResult::Cleanup(v)) => v,
+        _ => panic!("Expected Cleanup"),
+    };
+
+    assert_eq!(flow.unconsumed_input(), 5);
+}
+
+#[test]
+fn not_clean_with_excess_input_no_body() {
+    let scenario = Scenario::builder().head("https://a.test").build();
+
+    let mut flow = scenario.to_recv_response();
+
+    let mut input = write_response(&Response::new(()));
+    input.extend_from_slice(b"garbage");
+
+    flow.try_response(&input).unwrap();
+
+    let flow = match flow.proceed() {
+        Some(RecvResponseResult::Cleanup(v)) => v,
+        _ => panic!("Expected Cleanup"),
+    };
+
+    assert_eq!(flow.unconsumed_input(), 7);
+    assert!(!flow.is_clean_for_reuse());
+}
+
+#[test]
+fn not_clean_close_delimited() {
+    let scenario = Scenario::builder()
+        .request(
+            Request::get("https://a.test")
+                .version(Version::HTTP_10)
+                .body(())
+                .unwrap(),
+        )
+        .response(Response::new(()))
+        .build();
+
+    let mut flow = scenario.to_recv_body();
+
+    flow.read(b"hello", &mut [0; 1024]).unwrap();
+
+    let flow = match flow.proceed() {
+        Some(RecvBodyResult::Cleanup(v)) => v,
+        _ => panic!("Expected Cleanup"),
+    };
+
+    assert!(!flow.is_body_fully_read());
+    assert!(!flow.is_clean_for_reuse());
+}
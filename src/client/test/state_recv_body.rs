@@ -1,7 +1,8 @@
 use http::Response;
 
-use crate::client::flow::CloseReason;
+use crate::client::flow::{CloseReason, RecvBodyResult};
 use crate::client::test::TestSliceExt;
+use crate::Error;
 
 use super::scenario::Scenario;
 
@@ -158,3 +159,156 @@ fn recv_body_content_length() {
     assert_eq!(output[..output_used].as_str(), "lo");
     assert!(flow.can_proceed());
 }
+
+#[test]
+fn recv_body_chunked_trailers() {
+    let scenario = Scenario::builder()
+        .get("https://q.test")
+        .response(
+            Response::builder()
+                .header("transfer-encoding", "chunked")
+                .header("trailer", "grpc-status")
+                .body(())
+                .unwrap(),
+        )
+        .build();
+
+    let mut flow = scenario.to_recv_body();
+
+    let mut output = vec![0; 1024];
+
+    let (input_used, _) = flow.read(b"5\r\nhello\r\n0\r\n", &mut output).unwrap();
+    assert_eq!(input_used, 13);
+    assert!(flow.trailers().is_none());
+
+    let (input_used, output_used) = flow.read(b"grpc-status: 0\r\n\r\n", &mut output).unwrap();
+    assert_eq!(input_used, 18);
+    assert_eq!(output_used, 0);
+    assert!(flow.can_proceed());
+
+    let trailers = flow.trailers().unwrap();
+    assert_eq!(trailers.get("grpc-status").unwrap(), "0");
+}
+
+#[test]
+fn recv_body_not_chunked_no_trailers() {
+    let scenario = Scenario::builder()
+        .get("https://q.test")
+        .response(Response::new(()))
+        .recv_body("hello", false)
+        .build();
+
+    let mut flow = scenario.to_recv_body();
+
+    flow.read(b"hello", &mut vec![0; 1024]).unwrap();
+
+    assert!(flow.can_proceed());
+    assert!(flow.trailers().is_none());
+}
+
+#[test]
+fn recv_body_chunk_extensions() {
+    let scenario = Scenario::builder()
+        .get("https://q.test")
+        .response(
+            Response::builder()
+                .header("transfer-encoding", "chunked")
+                .body(())
+                .unwrap(),
+        )
+        .build();
+
+    let mut flow = scenario.to_recv_body();
+
+    let mut output = vec![0; 1024];
+
+    flow.stop_on_chunk_boundary(true);
+
+    const INPUT: &[u8] = b"5;sig=abc\r\nhello\r\n5\r\nworld\r\n0;last\r\n\r\n";
+
+    let (i1, o1) = flow.read(INPUT, &mut output).unwrap();
+    assert_eq!(output[..o1].as_str(), "hello");
+    let ext = flow.chunk_extensions();
+    assert_eq!(ext.len(), 1);
+    assert_eq!((ext[0].name(), ext[0].value()), ("sig", Some("abc")));
+
+    let (i2, o2) = flow.read(&INPUT[i1..], &mut output).unwrap();
+    assert_eq!(output[..o2].as_str(), "world");
+    assert!(flow.chunk_extensions().is_empty());
+
+    flow.read(&INPUT[i1 + i2..], &mut output).unwrap();
+    assert_eq!(flow.chunk_extensions()[0].name(), "last");
+    assert!(flow.can_proceed());
+}
+
+#[test]
+fn eof_ends_close_delimited() {
+    let scenario = Scenario::builder().get("https://q.test").build();
+
+    let mut flow = scenario.to_recv_body();
+
+    flow.read(b"hello", &mut [0; 1024]).unwrap();
+
+    flow.signal_eof().unwrap();
+    assert!(flow.can_proceed());
+
+    // Nothing more to read after EOF
+    let (i, o) = flow.read(b"more", &mut [0; 1024]).unwrap();
+    assert_eq!((i, o), (0, 0));
+
+    let flow = match flow.proceed() {
+        Some(RecvBodyResult::Cleanup(v)) => v,
+        _ => panic!("Expected Cleanup"),
+    };
+    assert!(flow.is_body_fully_read());
+    assert!(flow.must_close_connection());
+}
+
+#[test]
+fn eof_truncated_content_length() {
+    let scenario = Scenario::builder()
+        .get("https://q.test")
+        .response(Response::new(()))
+        .recv_body("hello", false)
+        .build();
+
+    let mut flow = scenario.to_recv_body();
+
+    flow.read(b"hel", &mut [0; 1024]).unwrap();
+
+    assert_eq!(flow.signal_eof(), Err(Error::IncompleteBody));
+    assert!(!flow.can_proceed());
+}
+
+#[test]
+fn eof_truncated_chunked() {
+    let scenario = Scenario::builder()
+        .get("https://q.test")
+        .response(Response::new(()))
+        .recv_body("hello", true)
+        .build();
+
+    let mut flow = scenario.to_recv_body();
+
+    // Missing the 0\r\n\r\n terminator
+    flow.read(b"5\r\nhello\r\n", &mut [0; 1024]).unwrap();
+
+    assert_eq!(flow.signal_eof(), Err(Error::IncompleteBody));
+    assert!(!flow.can_proceed());
+}
+
+#[test]
+fn eof_after_complete_body() {
+    let scenario = Scenario::builder()
+        .get("https://q.test")
+        .response(Response::new(()))
+        .recv_body("hello", false)
+        .build();
+
+    let mut flow = scenario.to_recv_body();
+
+    flow.read(b"hello", &mut [0; 1024]).unwrap();
+
+    assert_eq!(flow.signal_eof(), Ok(()));
+    assert!(flow.can_proceed());
+}
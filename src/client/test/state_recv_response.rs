@@ -1,7 +1,10 @@
 use http::{StatusCode, Version};
 
+use crate::client::flow::RecvResponseResult;
 use crate::client::test::scenario::Scenario;
 use crate::ext::HeaderIterExt;
+use crate::parser::ReasonPhrase;
+use crate::{BodyMode, Error, Limits, ParsePolicy};
 
 // This is a complete response.
 const RESPONSE: &[u8] = b"\
@@ -40,6 +43,27 @@ fn receive_complete_response() {
     assert_eq!(response.headers().get("content-length").unwrap(), "123");
     assert!(response.headers().iter().has("content-type", "text/plain"));
 
+    let reason = response.extensions().get::<ReasonPhrase>().unwrap();
+    assert_eq!(reason.as_str(), "OK");
+
+    assert!(flow.can_proceed());
+}
+
+#[test]
+fn receive_trickling_response() {
+    let scenario = Scenario::builder().get("https://q.test").build();
+    let mut flow = scenario.to_recv_response();
+
+    // Same flow, growing input.
+    for i in 0..RESPONSE.len() - 1 {
+        let (input_used, maybe_response) = flow.try_response(&RESPONSE[..i]).unwrap();
+        assert_eq!(input_used, 0);
+        assert!(maybe_response.is_none());
+    }
+
+    let (input_used, maybe_response) = flow.try_response(RESPONSE).unwrap();
+    assert_eq!(input_used, 66);
+    assert!(maybe_response.is_some());
     assert!(flow.can_proceed());
 }
 
@@ -90,3 +114,162 @@ fn expect_100_without_100_continue() {
     assert!(maybe_response.is_some());
     assert!(flow.can_proceed());
 }
+
+#[test]
+fn ambiguous_content_length() {
+    let scenario = Scenario::builder().get("https://q.test").build();
+    let mut flow = scenario.to_recv_response();
+
+    let err = flow
+        .try_response(b"HTTP/1.1 200 OK\r\ncontent-length: 5\r\ncontent-length: 6\r\n\r\n")
+        .unwrap_err();
+
+    assert_eq!(err, Error::TooManyContentLengthHeaders);
+}
+
+#[test]
+fn chunked_in_second_transfer_encoding() {
+    let scenario = Scenario::builder().get("https://q.test").build();
+    let mut flow = scenario.to_recv_response();
+
+    flow.try_response(
+        b"HTTP/1.1 200 OK\r\ntransfer-encoding: gzip\r\ntransfer-encoding: chunked\r\n\r\n",
+    )
+    .unwrap();
+
+    let flow = match flow.proceed() {
+        Some(RecvResponseResult::RecvBody(v)) => v,
+        _ => unreachable!(),
+    };
+
+    assert_eq!(flow.body_mode(), BodyMode::Chunked);
+}
+
+#[test]
+fn partial_redirect_policy() {
+    // No final \r\n after the headers.
+    const PARTIAL: &[u8] = b"HTTP/1.1 302 Found\r\nlocation: /other\r\n";
+
+    let scenario = Scenario::builder().get("https://q.test").build();
+    let mut flow = scenario.to_recv_response();
+
+    let (input_used, maybe_response) = flow.try_response(PARTIAL).unwrap();
+    assert_eq!(input_used, PARTIAL.len());
+    let response = maybe_response.unwrap();
+    assert_eq!(response.headers().get("connection").unwrap(), "close");
+
+    let scenario = Scenario::builder()
+        .get("https://q.test")
+        .policy(ParsePolicy::strict())
+        .build();
+    let mut flow = scenario.to_recv_response();
+
+    let (input_used, maybe_response) = flow.try_response(PARTIAL).unwrap();
+    assert_eq!(input_used, 0);
+    assert!(maybe_response.is_none());
+}
+
+#[test]
+fn limit_max_headers() {
+    let scenario = Scenario::builder()
+        .get("https://q.test")
+        .limits(Limits::new().max_headers(1))
+        .build();
+    let mut flow = scenario.to_recv_response();
+
+    let err = flow.try_response(RESPONSE).unwrap_err();
+    assert_eq!(err, Error::TooManyHeaders(1));
+}
+
+#[test]
+fn limit_max_line_len() {
+    let scenario = Scenario::builder()
+        .get("https://q.test")
+        .limits(Limits::new().max_line_len(20))
+        .build();
+    let mut flow = scenario.to_recv_response();
+
+    let err = flow.try_response(RESPONSE).unwrap_err();
+    assert_eq!(err, Error::HeaderLineTooLong(20));
+
+    // Also for an incomplete line.
+    let mut flow = scenario.to_recv_response();
+    let err = flow
+        .try_response(b"HTTP/1.1 200 OK\r\nx-long: abcdefghijklmn")
+        .unwrap_err();
+    assert_eq!(err, Error::HeaderLineTooLong(20));
+}
+
+#[test]
+fn limit_max_header_size() {
+    let scenario = Scenario::builder()
+        .get("https://q.test")
+        .limits(Limits::new().max_header_size(40))
+        .build();
+    let mut flow = scenario.to_recv_response();
+
+    // Not all headers yet, but already too much.
+    let err = flow.try_response(&RESPONSE[..50]).unwrap_err();
+    assert_eq!(err, Error::HeadersTooLarge(40));
+}
+
+#[test]
+fn interim_early_hints() {
+    let scenario = Scenario::builder().get("https://q.test").build();
+    let mut flow = scenario.to_recv_response();
+
+    let input = b"HTTP/1.1 103 Early Hints\r\n\
+        link: </style.css>; rel=preload; as=style\r\n\
+        \r\n\
+        HTTP/1.1 102 Processing\r\n\
+        \r\n";
+
+    let (input_used, maybe_response) = flow.try_response(input).unwrap();
+    assert_eq!(input_used, 71);
+    assert!(maybe_response.is_none());
+    assert!(!flow.can_proceed());
+
+    let (input_used, maybe_response) = flow.try_response(&input[71..]).unwrap();
+    assert_eq!(input_used, 27);
+    assert!(maybe_response.is_none());
+    assert!(!flow.can_proceed());
+
+    let interim = flow.take_interim_responses();
+    assert_eq!(interim.len(), 2);
+    assert_eq!(interim[0].status(), 103);
+    assert_eq!(
+        interim[0].headers().get("link").unwrap(),
+        "</style.css>; rel=preload; as=style"
+    );
+    assert_eq!(interim[1].status(), StatusCode::PROCESSING);
+    assert!(flow.take_interim_responses().is_empty());
+
+    // The final response follows.
+    let (input_used, maybe_response) = flow.try_response(RESPONSE).unwrap();
+    assert_eq!(input_used, 66);
+    assert_eq!(maybe_response.unwrap().status(), StatusCode::OK);
+    assert!(flow.can_proceed());
+}
+
+#[test]
+fn limit_interim_responses() {
+    let scenario = Scenario::builder()
+        .get("https://q.test")
+        .limits(Limits::new().max_interim_responses(2))
+        .build();
+    let mut flow = scenario.to_recv_response();
+
+    let input = b"HTTP/1.1 102 Processing\r\n\r\n";
+
+    flow.try_response(input).unwrap();
+    flow.try_response(input).unwrap();
+
+    let err = flow.try_response(input).unwrap_err();
+    assert_eq!(err, Error::TooManyInterimResponses(2));
+
+    // Taking them makes room for more.
+    assert_eq!(flow.take_interim_responses().len(), 2);
+    let (input_used, maybe_response) = flow.try_response(input).unwrap();
+    assert_eq!(input_used, 27);
+    assert!(maybe_response.is_none());
+}
@@ -335,3 +335,57 @@ fn dont_keep_content_length() {
             \r\n";
     assert_eq!(o[..n].as_str(), cmp);
 }
+
+#[test]
+fn keep_proxy_redirect_http() {
+    let scenario = Scenario::builder()
+        .get("http://a.test/foo")
+        .via_proxy(Some("Basic dXNlcjpwYXNz"))
+        .redirect(StatusCode::FOUND, "http://b.test/bar")
+        .build();
+
+    let mut flow = scenario
+        .to_redirect()
+        .as_new_flow(RedirectAuthHeaders::Never)
+        .unwrap()
+        .unwrap()
+        .proceed();
+
+    let mut o = vec![0; 1024];
+
+    let n = flow.write(&mut o).unwrap();
+
+    let cmp = "\
+            GET http://b.test/bar HTTP/1.1\r\n\
+            proxy-authorization: Basic dXNlcjpwYXNz\r\n\
+            host: b.test\r\n\
+            \r\n";
+    assert_eq!(o[..n].as_str(), cmp);
+}
+
+#[test]
+fn dont_keep_proxy_redirect_https() {
+    let scenario = Scenario::builder()
+        .get("http://a.test/foo")
+        .header("proxy-authorization", "Basic Zm9vOmJhcg==")
+        .via_proxy(Some("Basic dXNlcjpwYXNz"))
+        .redirect(StatusCode::FOUND, "https://a.test/bar")
+        .build();
+
+    let mut flow = scenario
+        .to_redirect()
+        .as_new_flow(RedirectAuthHeaders::Never)
+        .unwrap()
+        .unwrap()
+        .proceed();
+
+    let mut o = vec![0; 1024];
+
+    let n = flow.write(&mut o).unwrap();
+
+    let cmp = "\
+            GET /bar HTTP/1.1\r\n\
+            host: a.test\r\n\
+            \r\n";
+    assert_eq!(o[..n].as_str(), cmp);
+}
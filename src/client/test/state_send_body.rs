@@ -1,4 +1,7 @@
+use http::HeaderMap;
+
 use crate::client::flow::SendRequestResult;
+use crate::Error;
 
 use super::scenario::Scenario;
 use super::TestSliceExt;
@@ -141,3 +144,78 @@ fn send_body_despite_method() {
     // We should be able to get to this state without errors.
     assert!(matches!(result, SendRequestResult::SendBody(_)));
 }
+
+#[test]
+fn write_chunked_with_trailers() {
+    let scenario = Scenario::builder()
+        .post("https://q.test")
+        .header("trailer", "x-checksum, digest")
+        .build();
+
+    let mut flow = scenario.to_send_body();
+    let mut output = vec![0; 1024];
+
+    let (_, n1) = flow.write(b"hello", &mut output).unwrap();
+    assert!(!flow.can_proceed());
+
+    let mut trailers = HeaderMap::new();
+    trailers.insert("digest", "sha-256=abc".parse().unwrap());
+    trailers.insert("x-checksum", "123".parse().unwrap());
+
+    let n2 = flow.write_trailers(&trailers, &mut output[n1..]).unwrap();
+
+    assert_eq!(
+        output[..n1 + n2].as_str(),
+        "5\r\nhello\r\n0\r\ndigest: sha-256=abc\r\nx-checksum: 123\r\n\r\n"
+    );
+    assert!(flow.can_proceed());
+}
+
+#[test]
+fn write_trailers_not_announced() {
+    let scenario = Scenario::builder()
+        .post("https://q.test")
+        .header("trailer", "digest")
+        .build();
+
+    let mut flow = scenario.to_send_body();
+
+    let mut trailers = HeaderMap::new();
+    trailers.insert("x-other", "1".parse().unwrap());
+
+    let err = flow.write_trailers(&trailers, &mut [0; 1024]).unwrap_err();
+    assert_eq!(err, Error::TrailerNotAnnounced("x-other".to_string()));
+    assert!(!flow.can_proceed());
+}
+
+#[test]
+fn write_trailers_not_chunked() {
+    let scenario = Scenario::builder()
+        .post("https://q.test")
+        .header("content-length", 5)
+        .build();
+
+    let mut flow = scenario.to_send_body();
+
+    let err = flow
+        .write_trailers(&HeaderMap::new(), &mut [0; 1024])
+        .unwrap_err();
+    assert_eq!(err, Error::BodyNotChunked);
+}
+
+#[test]
+fn write_trailers_output_too_small() {
+    let scenario = Scenario::builder()
+        .post("https://q.test")
+        .header("trailer", "digest")
+        .build();
+
+    let mut flow = scenario.to_send_body();
+
+    let mut trailers = HeaderMap::new();
+    trailers.insert("digest", "sha-256=abc".parse().unwrap());
+
+    let err = flow.write_trailers(&trailers, &mut [0; 10]).unwrap_err();
+    assert_eq!(err, Error::OutputOverflow);
+    assert!(!flow.can_proceed());
+}
@@ -0,0 +1,103 @@
+use http::{Request, Response, StatusCode};
+
+use crate::client::flow::{Flow, RecvResponseResult};
+use crate::client::test::TestSliceExt;
+use crate::{Error, ParsePolicy};
+
+use super::scenario::{write_response, Scenario};
+
+#[test]
+fn connect_authority_form() {
+    let scenario = Scenario::builder().connect("https://q.test").build();
+
+    let mut flow = scenario.to_send_request();
+
+    let mut output = vec![0; 1024];
+    let n = flow.write(&mut output).unwrap();
+
+    assert_eq!(
+        output[..n].as_str(),
+        "CONNECT q.test:443 HTTP/1.1\r\nhost: q.test:443\r\n\r\n"
+    );
+}
+
+#[test]
+fn connect_authority_form_explicit_port() {
+    let scenario = Scenario::builder().connect("q.test:8080").build();
+
+    let mut flow = scenario.to_send_request();
+
+    let mut output = vec![0; 1024];
+    let n = flow.write(&mut output).unwrap();
+
+    assert_eq!(
+        output[..n].as_str(),
+        "CONNECT q.test:8080 HTTP/1.1\r\nhost: q.test:8080\r\n\r\n"
+    );
+}
+
+#[test]
+fn connect_without_authority() {
+    let request = Request::connect("/path").body(()).unwrap();
+
+    let mut flow = Flow::new(request, ParsePolicy::default())
+        .unwrap()
+        .proceed();
+
+    let err = flow.write(&mut [0; 1024]).unwrap_err();
+    assert_eq!(err, Error::ConnectMissingAuthority);
+}
+
+#[test]
+fn connect_success() {
+    let scenario = Scenario::builder()
+        .connect("https://q.test")
+        .response(Response::new(()))
+        .build();
+
+    let flow = scenario.to_tunnel();
+
+    assert!(flow.must_close_connection());
+    assert_eq!(flow.close_reason(), Some("connection is a CONNECT tunnel"));
+    assert!(flow.leftover().is_empty());
+}
+
+#[test]
+fn connect_leftover_input() {
+    let scenario = Scenario::builder().connect("https://q.test").build();
+
+    let mut flow = scenario.to_recv_response();
+
+    let mut input = write_response(&Response::new(()));
+    input.extend_from_slice(b"\x16\x03\x01");
+
+    let (input_used, response) = flow.try_response(&input).unwrap();
+    assert_eq!(input_used, input.len());
+    assert_eq!(response.unwrap().status(), StatusCode::OK);
+
+    let flow = match flow.proceed() {
+        Some(RecvResponseResult::Tunnel(v)) => v,
+        _ => panic!("2xx to CONNECT should result in Tunnel"),
+    };
+
+    assert_eq!(flow.leftover(), b"\x16\x03\x01");
+    assert_eq!(flow.into_leftover(), b"\x16\x03\x01");
+}
+
+#[test]
+fn connect_refused() {
+    let scenario = Scenario::builder()
+        .connect("https://q.test")
+        .response(
+            Response::builder()
+                .status(StatusCode::PROXY_AUTHENTICATION_REQUIRED)
+                .body(())
+                .unwrap(),
+        )
+        .recv_body("denied", false)
+        .build();
+
+    let flow = scenario.to_cleanup();
+
+    assert!(!flow.must_close_connection());
+}
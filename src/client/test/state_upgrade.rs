@@ -0,0 +1,127 @@
+use http::{Response, StatusCode};
+
+use crate::client::flow::RecvResponseResult;
+use crate::Error;
+
+use super::scenario::{write_response, Scenario};
+
+fn switching_protocols(upgrade: &str) -> Response<()> {
+    Response::builder()
+        .status(StatusCode::SWITCHING_PROTOCOLS)
+        .header("connection", "Upgrade")
+        .header("upgrade", upgrade)
+        .body(())
+        .unwrap()
+}
+
+#[test]
+fn upgrade_accepted() {
+    let scenario = Scenario::builder()
+        .get("https://q.test")
+        .header("connection", "upgrade")
+        .header("upgrade", "foo/2, websocket")
+        .response(switching_protocols("WebSocket"))
+        .build();
+
+    let flow = scenario.to_upgrade();
+
+    assert_eq!(flow.protocol(), "WebSocket");
+    assert!(flow.leftover().is_empty());
+}
+
+#[test]
+fn upgrade_leftover_input() {
+    let scenario = Scenario::builder()
+        .get("https://q.test")
+        .header("connection", "upgrade")
+        .header("upgrade", "websocket")
+        .build();
+
+    let mut flow = scenario.to_recv_response();
+
+    let mut input = write_response(&switching_protocols("websocket"));
+    input.extend_from_slice(b"new protocol");
+
+    let (input_used, response) = flow.try_response(&input).unwrap();
+    assert_eq!(input_used, input.len());
+    assert_eq!(response.unwrap().status(), StatusCode::SWITCHING_PROTOCOLS);
+
+    let flow = match flow.proceed() {
+        Some(RecvResponseResult::Upgrade(v)) => v,
+        _ => panic!("101 should result in Upgrade"),
+    };
+
+    assert_eq!(flow.leftover(), b"new protocol");
+    assert_eq!(flow.into_leftover(), b"new protocol");
+}
+
+#[test]
+fn upgrade_not_requested() {
+    let scenario = Scenario::builder().get("https://q.test").build();
+
+    let mut flow = scenario.to_recv_response();
+
+    let input = write_response(&switching_protocols("websocket"));
+    let err = flow.try_response(&input).unwrap_err();
+
+    assert_eq!(err, Error::UpgradeNotRequested);
+}
+
+#[test]
+fn upgrade_other_protocol() {
+    let scenario = Scenario::builder()
+        .get("https://q.test")
+        .header("connection", "upgrade")
+        .header("upgrade", "websocket")
+        .build();
+
+    let mut flow = scenario.to_recv_response();
+
+    let input = write_response(&switching_protocols("h2c"));
+    let err = flow.try_response(&input).unwrap_err();
+
+    assert_eq!(
+        err,
+        Error::BadUpgradeResponse("protocol not requested: h2c".to_string())
+    );
+}
+
+#[test]
+fn upgrade_missing_connection_header() {
+    let scenario = Scenario::builder()
+        .get("https://q.test")
+        .header("connection", "upgrade")
+        .header("upgrade", "websocket")
+        .build();
+
+    let mut flow = scenario.to_recv_response();
+
+    let response = Response::builder()
+        .status(StatusCode::SWITCHING_PROTOCOLS)
+        .header("upgrade", "websocket")
+        .body(())
+        .unwrap();
+
+    let err = flow.try_response(&write_response(&response)).unwrap_err();
+
+    assert_eq!(
+        err,
+        Error::BadUpgradeResponse("missing connection: upgrade".to_string())
+    );
+}
+
+#[test]
+fn upgrade_declined() {
+    // Server is free to ignore the upgrade request.
+    let scenario = Scenario::builder()
+        .get("https://q.test")
+        .header("connection", "upgrade")
+        .header("upgrade", "websocket")
+        .response(Response::new(()))
+        .recv_body("hello", false)
+        .build();
+
+    let flow = scenario.to_cleanup();
+
+    assert!(!flow.must_close_connection());
+}
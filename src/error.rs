@@ -19,6 +19,9 @@ pub enum Error {
     ChunkLenNotAscii,
     ChunkLenNotANumber,
     ChunkExpectedCrLf,
+    ChunkExtensionInvalid,
+    ChunkLineTooLong(usize),
+    TooManyChunkExtensions(usize),
     BodyContentAfterFinish,
     BodyLargerThanContentLength,
     UnfinishedRequest,
@@ -32,8 +35,28 @@ pub enum Error {
     BadLocationHeader(String),
     HeadersWith100,
     BodyIsChunked,
+    BodyIsCloseDelimited,
     RequestMissingMethod,
     RequestInvalidMethod,
+    RequestInvalidUri,
+    BodyNotChunked,
+    TrailerNotAnnounced(String),
+    UpgradeNotRequested,
+    BadUpgradeResponse(String),
+    BadWebSocketHandshake(String),
+    BadWebSocketFrame(String),
+    ConnectMissingAuthority,
+    ProxyRequiresAbsoluteUri,
+    NotPipelinable(Method),
+    PipelineAborted,
+    IncompleteBody,
+    ContentLengthAndTransferEncoding,
+    BadTransferEncodingHeader,
+    TooManyHeaders(usize),
+    HeadersTooLarge(usize),
+    HeaderLineTooLong(usize),
+    TrailersTooLarge(usize),
+    TooManyInterimResponses(usize),
 }
 
 impl From<httparse::Error> for Error {
@@ -62,6 +85,9 @@ impl fmt::Display for Error {
             Error::ChunkLenNotAscii => write!(f, "chunk length is not ascii"),
             Error::ChunkLenNotANumber => write!(f, "chunk length cannot be read as a number"),
             Error::ChunkExpectedCrLf => write!(f, "chunk expected crlf as next character"),
+            Error::ChunkExtensionInvalid => write!(f, "chunk extension is malformed"),
+            Error::ChunkLineTooLong(v) => write!(f, "chunk size line longer than {} bytes", v),
+            Error::TooManyChunkExtensions(v) => write!(f, "more than {} chunk extensions", v),
             Error::BodyContentAfterFinish => {
                 write!(f, "attempt to stream body after sending finish (&[])")
             }
@@ -79,8 +105,34 @@ impl fmt::Display for Error {
             Error::BadLocationHeader(v) => write!(f, "location header is malformed: {}", v),
             Error::HeadersWith100 => write!(f, "received headers with 100-continue response"),
             Error::BodyIsChunked => write!(f, "body is chunked"),
+            Error::BodyIsCloseDelimited => write!(f, "body is close delimited"),
             Error::RequestMissingMethod => write!(f, "http request is missing a method"),
             Error::RequestInvalidMethod => write!(f, "http request invalid method"),
+            Error::RequestInvalidUri => write!(f, "http request invalid uri"),
+            Error::BodyNotChunked => write!(f, "body is not chunked"),
+            Error::TrailerNotAnnounced(v) => {
+                write!(f, "trailer not announced in trailer header: {}", v)
+            }
+            Error::UpgradeNotRequested => write!(f, "101 switching protocols without upgrade"),
+            Error::BadUpgradeResponse(v) => write!(f, "bad upgrade response: {}", v),
+            Error::BadWebSocketHandshake(v) => write!(f, "bad websocket handshake: {}", v),
+            Error::BadWebSocketFrame(v) => write!(f, "bad websocket frame: {}", v),
+            Error::ConnectMissingAuthority => write!(f, "CONNECT request uri has no authority"),
+            Error::ProxyRequiresAbsoluteUri => {
+                write!(f, "request via proxy requires scheme and host in uri")
+            }
+            Error::NotPipelinable(v) => write!(f, "request can't be pipelined: {}", v),
+            Error::PipelineAborted => write!(f, "pipeline is aborted"),
+            Error::IncompleteBody => write!(f, "connection closed before end of body"),
+            Error::ContentLengthAndTransferEncoding => {
+                write!(f, "both content-length and transfer-encoding headers")
+            }
+            Error::BadTransferEncodingHeader => write!(f, "transfer-encoding header is malformed"),
+            Error::TooManyHeaders(v) => write!(f, "more than {} headers", v),
+            Error::HeadersTooLarge(v) => write!(f, "headers larger than {} bytes", v),
+            Error::HeaderLineTooLong(v) => write!(f, "header line longer than {} bytes", v),
+            Error::TrailersTooLarge(v) => write!(f, "trailers larger than {} bytes", v),
+            Error::TooManyInterimResponses(v) => write!(f, "more than {} interim responses", v),
         }
     }
 }
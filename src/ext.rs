@@ -45,6 +45,7 @@ impl MethodExt for Method {
 pub(crate) trait HeaderIterExt {
     fn has(self, key: &str, value: &str) -> bool;
     fn has_expect_100(self) -> bool;
+    fn has_token(self, key: &str, token: &str) -> bool;
 }
 
 impl<'a, I: Iterator<Item = (&'a HeaderName, &'a HeaderValue)>> HeaderIterExt for I {
@@ -52,11 +53,31 @@ impl<'a, I: Iterator<Item = (&'a HeaderName, &'a HeaderValue)>> HeaderIterExt fo
         self.filter(|i| i.0 == key).any(|i| i.1 == value)
     }
 
+    fn has_token(self, key: &str, token: &str) -> bool {
+        header_tokens(self, key).any(|v| v.eq_ignore_ascii_case(token))
+    }
+
     fn has_expect_100(self) -> bool {
         self.has("expect", "100-continue")
     }
 }
 
+/// All comma separated values of (possibly repeated) `key` headers.
+pub(crate) fn header_tokens<'a, 'b>(
+    headers: impl Iterator<Item = (&'a HeaderName, &'a HeaderValue)> + 'b,
+    key: &'b str,
+) -> impl Iterator<Item = &'a str> + 'b
+where
+    'a: 'b,
+{
+    headers
+        .filter(move |i| i.0 == key)
+        .filter_map(|i| i.1.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(|v| v.trim())
+        .filter(|v| !v.is_empty())
+}
+
 pub(crate) trait StatusExt {
     /// Detect 307/308 redirect
     fn is_redirect_retaining_status(&self) -> bool;
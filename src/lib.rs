@@ -19,14 +19,30 @@ mod error;
 pub use error::Error;
 
 mod chunk;
+pub use chunk::ChunkExtension;
+
 mod ext;
+mod redact;
 mod util;
 
 mod body;
 pub use body::BodyMode;
 
+mod policy;
+pub use policy::ParsePolicy;
+
+mod limits;
+pub use limits::Limits;
+
+mod casing;
+pub use casing::HeaderCasing;
+
 pub mod client;
 
+pub mod server;
+
+pub mod websocket;
+
 /// Low level HTTP parser
 ///
 /// This is to bridge `httparse` crate to `http` crate.
@@ -0,0 +1,105 @@
+use crate::client::MAX_RESPONSE_HEADERS;
+
+/// Limits on the amount of header data received.
+///
+/// A response is buffered until all headers are received. Without limits, a malicious
+/// server could make us buffer an unbounded amount of data. Each limit produces its own
+/// [`Error`](crate::Error) variant when exceeded.
+///
+/// The limits are set on a flow in the `Prepare` state via
+/// [`Flow::set_limits()`](crate::client::flow::Flow::set_limits). On the server side,
+/// they apply to the received request in the same way, and are set via
+/// [`server::flow::Flow::set_limits()`](crate::server::flow::Flow::set_limits).
+///
+/// ```
+/// use ureq_proto::Limits;
+///
+/// let limits = Limits::new().max_headers(32).max_line_len(1024);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    pub(crate) max_headers: usize,
+    pub(crate) max_header_size: usize,
+    pub(crate) max_line_len: usize,
+    pub(crate) max_trailer_size: usize,
+    pub(crate) max_chunk_extensions: usize,
+    pub(crate) max_interim_responses: usize,
+}
+
+impl Limits {
+    /// Create limits with the default values.
+    ///
+    /// * 128 headers ([`MAX_RESPONSE_HEADERS`])
+    /// * 64kB header block
+    /// * 8kB line length
+    /// * 8kB trailers
+    /// * 16 chunk extensions
+    /// * 16 interim responses
+    pub const fn new() -> Self {
+        Limits {
+            max_headers: MAX_RESPONSE_HEADERS,
+            max_header_size: 64 * 1024,
+            max_line_len: 8 * 1024,
+            max_trailer_size: 8 * 1024,
+            max_chunk_extensions: 16,
+            max_interim_responses: 16,
+        }
+    }
+
+    /// Max number of headers in a response, or a request for a server.
+    ///
+    /// Exceeding it is [`Error::TooManyHeaders`](crate::Error::TooManyHeaders).
+    pub const fn max_headers(mut self, value: usize) -> Self {
+        self.max_headers = value;
+        self
+    }
+
+    /// Max number of bytes for the status line and all headers, including line endings.
+    ///
+    /// Exceeding it is [`Error::HeadersTooLarge`](crate::Error::HeadersTooLarge).
+    pub const fn max_header_size(mut self, value: usize) -> Self {
+        self.max_header_size = value;
+        self
+    }
+
+    /// Max number of bytes for a single line, such as the status line or a header.
+    ///
+    /// Exceeding it is [`Error::HeaderLineTooLong`](crate::Error::HeaderLineTooLong). This
+    /// also limits the chunk size line, including any chunk extensions, in a chunked body.
+    /// Exceeding that is [`Error::ChunkLineTooLong`](crate::Error::ChunkLineTooLong).
+    pub const fn max_line_len(mut self, value: usize) -> Self {
+        self.max_line_len = value;
+        self
+    }
+
+    /// Max number of bytes for all trailer fields after a chunked body.
+    ///
+    /// Exceeding it is [`Error::TrailersTooLarge`](crate::Error::TrailersTooLarge).
+    pub const fn max_trailer_size(mut self, value: usize) -> Self {
+        self.max_trailer_size = value;
+        self
+    }
+
+    /// Max number of extensions on a chunk in a chunked body.
+    ///
+    /// Exceeding it is [`Error::TooManyChunkExtensions`](crate::Error::TooManyChunkExtensions).
+    pub const fn max_chunk_extensions(mut self, value: usize) -> Self {
+        self.max_chunk_extensions = value;
+        self
+    }
+
+    /// Max number of interim (1xx) responses held by a flow until taken.
+    ///
+    /// See [`Flow::take_interim_responses()`](crate::client::flow::Flow::take_interim_responses).
+    /// Exceeding it is [`Error::TooManyInterimResponses`](crate::Error::TooManyInterimResponses).
+    pub const fn max_interim_responses(mut self, value: usize) -> Self {
+        self.max_interim_responses = value;
+        self
+    }
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self::new()
+    }
+}
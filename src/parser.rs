@@ -1,7 +1,33 @@
-use http::{Method, Request, Response, StatusCode, Version};
-use httparse::Status;
+use std::borrow::Cow;
 
-use crate::Error;
+use http::{Method, Request, Response, StatusCode, Uri, Version};
+use httparse::{ParserConfig, Status};
+
+use crate::{Error, Limits, ParsePolicy};
+
+/// The reason phrase of a response, i.e. `Not Found` in `HTTP/1.1 404 Not Found`.
+///
+/// The [`http::Response`] has no place for the reason phrase, so it is added as
+/// an extension by [`try_parse_response()`] and [`try_parse_partial_response()`].
+///
+/// ```
+/// use ureq_proto::parser::{try_parse_response, ReasonPhrase};
+///
+/// let input = b"HTTP/1.1 404 No such thing\r\n\r\n";
+/// let (_, response) = try_parse_response::<10>(input).unwrap().unwrap();
+///
+/// let reason = response.extensions().get::<ReasonPhrase>().unwrap();
+/// assert_eq!(reason.as_str(), "No such thing");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReasonPhrase(String);
+
+impl ReasonPhrase {
+    /// The reason phrase as sent by the server.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
 
 /// Parse bytes into a complete response.
 ///
@@ -16,14 +42,35 @@ use crate::Error;
 ///
 /// The const `N` is the number of headers to max expect. If the input has more
 /// headers than `N` you get an error [`Error::HttpParseTooManyHeaders`].
+///
+/// Obsolete line folding is not accepted. See [`try_parse_response_with_policy()`].
 pub fn try_parse_response<const N: usize>(
     input: &[u8],
+) -> Result<Option<(usize, Response<()>)>, Error> {
+    try_parse_response_with_policy::<N>(input, &ParsePolicy::strict())
+}
+
+/// Like [`try_parse_response()`], with a policy for how strictly the response is parsed.
+///
+/// The `policy` decides whether obsolete line folding is accepted.
+pub fn try_parse_response_with_policy<const N: usize>(
+    input: &[u8],
+    policy: &ParsePolicy,
 ) -> Result<Option<(usize, Response<()>)>, Error> {
     let mut headers = [httparse::EMPTY_HEADER; N]; // 100 headers ~3kb
 
-    let mut res = httparse::Response::new(&mut headers);
+    try_parse_response_with(input, policy, &mut headers)
+}
+
+/// Like [`try_parse_response()`], with the header buffer sized at runtime.
+pub(crate) fn try_parse_response_with<'a>(
+    input: &'a [u8],
+    policy: &ParsePolicy,
+    headers: &mut [httparse::Header<'a>],
+) -> Result<Option<(usize, Response<()>)>, Error> {
+    let mut res = httparse::Response::new(headers);
 
-    let maybe_input_used = match res.parse(input) {
+    let maybe_input_used = match response_config(policy).parse_response(&mut res, input) {
         Ok(v) => v,
         Err(e) => {
             return Err(if e == httparse::Error::TooManyHeaders {
@@ -57,8 +104,12 @@ pub fn try_parse_response<const N: usize>(
 
     let mut builder = Response::builder().version(version).status(status);
 
+    if let Some(reason) = res.reason {
+        builder = builder.extension(ReasonPhrase(reason.to_string()));
+    }
+
     for h in res.headers {
-        builder = builder.header(h.name, h.value);
+        builder = builder.header(h.name, &*unfold(h.value));
     }
 
     let response = builder.body(()).expect("a valid response");
@@ -73,12 +124,23 @@ pub fn try_parse_response<const N: usize>(
 ///
 /// The const `N` is the number of headers to max expect. If the input has more
 /// headers than `N` you get an error [`Error::HttpParseTooManyHeaders`].
+///
+/// Obsolete line folding is not accepted, since the last header of a partial
+/// response could always be continued on the next line.
 pub fn try_parse_partial_response<const N: usize>(
     input: &[u8],
 ) -> Result<Option<Response<()>>, Error> {
     let mut headers = [httparse::EMPTY_HEADER; N]; // 100 headers ~3kb
 
-    let mut res = httparse::Response::new(&mut headers);
+    try_parse_partial_response_with(input, &mut headers)
+}
+
+/// Like [`try_parse_partial_response()`], with the header buffer sized at runtime.
+pub(crate) fn try_parse_partial_response_with<'a>(
+    input: &'a [u8],
+    headers: &mut [httparse::Header<'a>],
+) -> Result<Option<Response<()>>, Error> {
+    let mut res = httparse::Response::new(headers);
 
     match res.parse(input) {
         Ok(_) => {}
@@ -112,6 +174,10 @@ pub fn try_parse_partial_response<const N: usize>(
 
     let mut builder = Response::builder().version(version).status(status);
 
+    if let Some(reason) = res.reason {
+        builder = builder.extension(ReasonPhrase(reason.to_string()));
+    }
+
     for h in res.headers {
         if h.name.is_empty() || h.value.is_empty() {
             break;
@@ -124,6 +190,18 @@ pub fn try_parse_partial_response<const N: usize>(
     Ok(Some(response))
 }
 
+/// The status code of a response, as soon as the status line is received.
+///
+/// The headers need not be complete.
+pub(crate) fn try_parse_status(input: &[u8]) -> Option<StatusCode> {
+    let mut res = httparse::Response::new(&mut []);
+
+    // Parsing stops at the first header, but the status code is set before that.
+    let _ = res.parse(input);
+
+    res.code.and_then(|v| StatusCode::from_u16(v).ok())
+}
+
 /// Parse bytes into a complete request.
 ///
 /// Complete means that the last HTTP header is followed by an `\r\n`.
@@ -142,7 +220,15 @@ pub fn try_parse_request<const N: usize>(
 ) -> Result<Option<(usize, Request<()>)>, Error> {
     let mut headers = [httparse::EMPTY_HEADER; N]; // 100 headers ~3kb
 
-    let mut req = httparse::Request::new(&mut headers);
+    try_parse_request_with(input, &mut headers)
+}
+
+/// Like [`try_parse_request()`], with the header buffer sized at runtime.
+pub(crate) fn try_parse_request_with<'a>(
+    input: &'a [u8],
+    headers: &mut [httparse::Header<'a>],
+) -> Result<Option<(usize, Request<()>)>, Error> {
+    let mut req = httparse::Request::new(headers);
 
     let maybe_input_used = match req.parse(input) {
         Ok(v) => v,
@@ -176,20 +262,129 @@ pub fn try_parse_request<const N: usize>(
         Method::from_bytes(v.as_bytes()).map_err(|_| Error::RequestInvalidMethod)?
     };
 
-    let mut builder = Request::builder().version(version).method(method);
+    let uri = {
+        let v = req.path.ok_or(Error::RequestInvalidUri)?;
+        v.parse::<Uri>().map_err(|_| Error::RequestInvalidUri)?
+    };
+
+    let mut builder = Request::builder().version(version).method(method).uri(uri);
 
     for h in req.headers {
         builder = builder.header(h.name, h.value);
     }
 
-    let request = builder.body(()).expect("a valid response");
+    let request = builder.body(()).expect("a valid request");
 
     Ok(Some((input_used, request)))
 }
 
+/// Incremental search for the end of a header block.
+///
+/// Remembers how far the input has been scanned, so that calling it repeatedly
+/// with a growing buffer only examines the newly appended bytes. This avoids
+/// re-parsing the entire buffer when the headers trickle in slowly.
+///
+/// The [`Limits`] on line length and header block size are checked as the bytes
+/// are scanned.
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct HeadScanner {
+    /// Number of bytes scanned so far.
+    scanned: usize,
+    /// Start of the current (incomplete) line.
+    line_start: usize,
+    /// Whether a non-empty line has been seen. Empty lines before the
+    /// status line are not the end of the headers.
+    seen_line: bool,
+}
+
+impl HeadScanner {
+    /// Scan the input for the end of the header block.
+    ///
+    /// The input is expected to be the same buffer as in the previous call, possibly
+    /// with more bytes appended. If it is shorter, the scan starts over.
+    ///
+    /// Returns the length of the header block, including the final empty line,
+    /// once it is complete.
+    pub fn scan(&mut self, input: &[u8], limits: &Limits) -> Result<Option<usize>, Error> {
+        if input.len() < self.scanned {
+            *self = Self::default();
+        }
+
+        for (i, c) in input.iter().enumerate().skip(self.scanned) {
+            if *c != b'\n' {
+                continue;
+            }
+
+            let line = &input[self.line_start..i];
+            let line = line.strip_suffix(b"\r").unwrap_or(line);
+
+            if line.len() > limits.max_line_len {
+                return Err(Error::HeaderLineTooLong(limits.max_line_len));
+            }
+
+            let end = i + 1;
+
+            if end > limits.max_header_size {
+                return Err(Error::HeadersTooLarge(limits.max_header_size));
+            }
+
+            self.line_start = end;
+
+            if !line.is_empty() {
+                self.seen_line = true;
+            } else if self.seen_line {
+                *self = Self::default();
+                return Ok(Some(end));
+            }
+        }
+
+        self.scanned = input.len();
+
+        // The last line is incomplete, but can still be too long.
+        if input.len() - self.line_start > limits.max_line_len {
+            return Err(Error::HeaderLineTooLong(limits.max_line_len));
+        }
+
+        if input.len() > limits.max_header_size {
+            return Err(Error::HeadersTooLarge(limits.max_header_size));
+        }
+
+        Ok(None)
+    }
+}
+
+fn response_config(policy: &ParsePolicy) -> ParserConfig {
+    let mut config = ParserConfig::default();
+    config.allow_obsolete_multiline_headers_in_responses(policy.allow_obs_fold);
+    config
+}
+
+/// Replace obsolete line folding with spaces (RFC 9112 5.2).
+///
+/// httparse keeps the `\r\n` of an obs-fold in the header value, which
+/// is not a valid [`http::HeaderValue`].
+fn unfold(value: &[u8]) -> Cow<'_, [u8]> {
+    if !value.iter().any(|c| *c == b'\r' || *c == b'\n') {
+        return Cow::Borrowed(value);
+    }
+
+    let unfolded = value
+        .iter()
+        .map(|c| if *c == b'\r' || *c == b'\n' { b' ' } else { *c })
+        .collect();
+
+    Cow::Owned(unfolded)
+}
+
 #[cfg(test)]
 mod test {
-    use crate::parser::try_parse_response;
+    use http::StatusCode;
+
+    use crate::parser::{
+        try_parse_partial_response, try_parse_request, try_parse_response,
+        try_parse_response_with_policy, try_parse_status, HeadScanner, ReasonPhrase,
+    };
+    use crate::{Error, Limits, ParsePolicy};
 
     #[test]
     fn ensure_no_half_response() {
@@ -199,4 +394,103 @@ mod test {
 
         try_parse_response::<0>(bytes.as_bytes()).expect_err("too many headers");
     }
+
+    #[test]
+    fn status_before_headers() {
+        assert_eq!(try_parse_status(b"HTTP/1.1 30"), None);
+        assert_eq!(
+            try_parse_status(b"HTTP/1.1 302 Found\r\nLocation: /"),
+            Some(StatusCode::FOUND)
+        );
+        assert_eq!(
+            try_parse_status(b"HTTP/1.1 200 OK\r\n"),
+            Some(StatusCode::OK)
+        );
+    }
+
+    #[test]
+    fn reason_phrase() {
+        let bytes = b"HTTP/1.1 302 Go elsewhere\r\nLocation: /x\r\n";
+
+        let response = try_parse_partial_response::<10>(bytes).unwrap().unwrap();
+        let reason = response.extensions().get::<ReasonPhrase>().unwrap();
+        assert_eq!(reason.as_str(), "Go elsewhere");
+    }
+
+    #[test]
+    fn head_scanner_incremental() {
+        let bytes = b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello";
+        let limits = Limits::new();
+        let mut scanner = HeadScanner::default();
+
+        // Feed one byte at a time.
+        for i in 0..38 {
+            assert_eq!(scanner.scan(&bytes[..i], &limits), Ok(None));
+        }
+
+        assert_eq!(scanner.scan(&bytes[..38], &limits), Ok(Some(38)));
+
+        // Starts over after a complete header block.
+        assert_eq!(scanner.scan(&bytes[..30], &limits), Ok(None));
+    }
+
+    #[test]
+    fn head_scanner_limits() {
+        let limits = Limits::new().max_header_size(30).max_line_len(10);
+
+        let mut scanner = HeadScanner::default();
+        assert_eq!(scanner.scan(b"0123456789\r\n0123", &limits), Ok(None));
+
+        let mut scanner = HeadScanner::default();
+        assert_eq!(
+            scanner.scan(b"0123456789A\r\n", &limits),
+            Err(Error::HeaderLineTooLong(10))
+        );
+
+        // Incomplete line
+        let mut scanner = HeadScanner::default();
+        assert_eq!(scanner.scan(b"0123\r\n0123", &limits), Ok(None));
+        assert_eq!(
+            scanner.scan(b"0123\r\n0123456789A", &limits),
+            Err(Error::HeaderLineTooLong(10))
+        );
+
+        let mut scanner = HeadScanner::default();
+        assert_eq!(
+            scanner.scan(b"01234\r\n01234\r\n01234\r\n01234\r\n01234\r\n", &limits),
+            Err(Error::HeadersTooLarge(30))
+        );
+    }
+
+    #[test]
+    fn obs_fold() {
+        let bytes = "HTTP/1.1 200 OK\r\n\
+            X-Folded: one\r\n two\r\n\
+            Content-Length: 0\r\n\r\n";
+
+        let (_, response) =
+            try_parse_response_with_policy::<10>(bytes.as_bytes(), &ParsePolicy::lenient())
+                .unwrap()
+                .unwrap();
+
+        assert_eq!(response.headers().get("x-folded").unwrap(), "one   two");
+
+        let err = try_parse_response_with_policy::<10>(bytes.as_bytes(), &ParsePolicy::default())
+            .unwrap_err();
+        assert!(matches!(err, Error::HttpParseFail(_)));
+
+        let err = try_parse_response::<10>(bytes.as_bytes()).unwrap_err();
+        assert!(matches!(err, Error::HttpParseFail(_)));
+    }
+
+    #[test]
+    fn request_keeps_uri() {
+        let bytes = "GET /path?query=1 HTTP/1.1\r\n\
+            Host: q.test\r\n\r\n";
+
+        let (input_used, request) = try_parse_request::<10>(bytes.as_bytes()).unwrap().unwrap();
+
+        assert_eq!(input_used, 44);
+        assert_eq!(request.uri(), "/path?query=1");
+    }
 }
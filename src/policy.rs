@@ -0,0 +1,116 @@
+/// How strictly incoming HTTP/1.1 messages are parsed.
+///
+/// Real world servers don't always follow RFC 9112. A lenient policy accepts some
+/// well known deviations, while a strict policy rejects them. Being strict is
+/// safer for security-sensitive uses, since any leniency in framing is a possible
+/// source of disagreement between two HTTP implementations.
+///
+/// The policy is given to [`client::flow::Flow::new()`](crate::client::flow::Flow::new)
+/// or [`server::flow::Flow::new()`](crate::server::flow::Flow::new). The default is
+/// [`ParsePolicy::lenient()`] without obsolete line folding, which is how messages
+/// were parsed before there was a policy.
+///
+/// A custom policy starts from one of the presets:
+///
+/// ```
+/// use ureq_proto::ParsePolicy;
+///
+/// let policy = ParsePolicy::strict().allow_partial_redirect(true);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParsePolicy {
+    pub(crate) allow_partial_redirect: bool,
+    pub(crate) allow_obs_fold: bool,
+    pub(crate) max_chunk_size_len: usize,
+    pub(crate) ignore_invalid_chunk_extensions: bool,
+    pub(crate) allow_content_length_with_transfer_encoding: bool,
+}
+
+impl ParsePolicy {
+    /// Follow RFC 9112 to the letter.
+    ///
+    /// * Partial redirect responses are not accepted.
+    /// * Obsolete line folding in response headers is an error.
+    /// * The chunk size can be at most 16 characters.
+    /// * Malformed chunk extensions are an error.
+    /// * A response with both `content-length` and `transfer-encoding` is an error.
+    pub const fn strict() -> Self {
+        ParsePolicy {
+            allow_partial_redirect: false,
+            allow_obs_fold: false,
+            // 16 hex digits is a u64.
+            max_chunk_size_len: 16,
+            ignore_invalid_chunk_extensions: false,
+            allow_content_length_with_transfer_encoding: false,
+        }
+    }
+
+    /// Accept common deviations from RFC 9112.
+    ///
+    /// * Partial redirect responses are accepted.
+    /// * Obsolete line folding in response headers is replaced by spaces.
+    /// * The chunk size, including surrounding whitespace, can be at most 20 characters.
+    /// * Malformed chunk extensions are ignored.
+    /// * A response with both `content-length` and `transfer-encoding` uses the
+    ///   `transfer-encoding`.
+    pub const fn lenient() -> Self {
+        ParsePolicy {
+            allow_partial_redirect: true,
+            allow_obs_fold: true,
+            max_chunk_size_len: 20,
+            ignore_invalid_chunk_extensions: true,
+            allow_content_length_with_transfer_encoding: true,
+        }
+    }
+
+    /// Accept a redirect response where the headers are not terminated.
+    ///
+    /// Some broken servers send a redirect without the final `\r\n` after the headers.
+    /// If the status and `location` header is received, the response is used with a
+    /// synthetic `connection: close`, since the connection can't be reused.
+    pub const fn allow_partial_redirect(mut self, value: bool) -> Self {
+        self.allow_partial_redirect = value;
+        self
+    }
+
+    /// Accept obsolete line folding (obs-fold) in response headers.
+    ///
+    /// A header value continued on the next line starting with whitespace is
+    /// deprecated by RFC 9112 5.2. When accepted, the folds are replaced by spaces.
+    pub const fn allow_obs_fold(mut self, value: bool) -> Self {
+        self.allow_obs_fold = value;
+        self
+    }
+
+    /// Max number of characters for the size of a chunk in chunked transfer encoding.
+    ///
+    /// This excludes any chunk extensions.
+    pub const fn max_chunk_size_len(mut self, value: usize) -> Self {
+        self.max_chunk_size_len = value;
+        self
+    }
+
+    /// Ignore malformed chunk extensions instead of failing.
+    ///
+    /// A chunk with malformed extensions is read as if it had no extensions.
+    pub const fn ignore_invalid_chunk_extensions(mut self, value: bool) -> Self {
+        self.ignore_invalid_chunk_extensions = value;
+        self
+    }
+
+    /// Accept a response with both `content-length` and `transfer-encoding`.
+    ///
+    /// RFC 9112 6.3 says the `transfer-encoding` overrides the `content-length`, but
+    /// also that such a message ought to be handled as an error. A request like this is
+    /// always an error.
+    pub const fn allow_content_length_with_transfer_encoding(mut self, value: bool) -> Self {
+        self.allow_content_length_with_transfer_encoding = value;
+        self
+    }
+}
+
+impl Default for ParsePolicy {
+    fn default() -> Self {
+        Self::lenient().allow_obs_fold(false)
+    }
+}
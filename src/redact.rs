@@ -0,0 +1,168 @@
+use std::borrow::Cow;
+use std::sync::Arc;
+
+use http::header::{AUTHORIZATION, COOKIE, PROXY_AUTHORIZATION, SET_COOKIE};
+use http::HeaderName;
+
+/// Headers with values masked in the trace logging.
+///
+/// With `trace` level enabled for this crate, all bytes sent and received are logged.
+/// The values of these headers are replaced by `*` before anything reaches the `log`
+/// crate. The default is `authorization`, `proxy-authorization`, `cookie` and `set-cookie`.
+///
+/// It is cheap to clone, since each [`Writer`](crate::util::Writer) holds a copy.
+#[derive(Debug, Clone)]
+pub(crate) struct RedactedHeaders(Arc<[HeaderName]>);
+
+impl RedactedHeaders {
+    pub fn new(names: Vec<HeaderName>) -> Self {
+        RedactedHeaders(names.into())
+    }
+
+    /// Mask the values of redacted headers in data about to be logged.
+    pub fn redact<'a>(&self, data: &'a [u8]) -> Cow<'a, [u8]> {
+        redact_with(data, &self.0)
+    }
+}
+
+impl Default for RedactedHeaders {
+    fn default() -> Self {
+        RedactedHeaders::new(vec![AUTHORIZATION, PROXY_AUTHORIZATION, COOKIE, SET_COOKIE])
+    }
+}
+
+/// Header lines are recognized at the start of the data and after each `\n`.
+fn redact_with<'a>(data: &'a [u8], names: &[HeaderName]) -> Cow<'a, [u8]> {
+    let mut data = Cow::Borrowed(data);
+    let mut start = 0;
+
+    // Whether the previous line was a redacted header, which makes
+    // any folded continuation line (obs-fold) part of the value.
+    let mut in_redacted = false;
+
+    while start < data.len() {
+        let end = data[start..]
+            .iter()
+            .position(|c| *c == b'\n')
+            .map(|p| start + p)
+            .unwrap_or(data.len());
+
+        let line = &data[start..end];
+
+        let range = if is_continuation(line) {
+            if in_redacted {
+                Some(continued_value(line))
+            } else {
+                None
+            }
+        } else {
+            redacted_value(line, names)
+        };
+
+        in_redacted = range.is_some();
+
+        if let Some(range) = range {
+            let owned = data.to_mut();
+            for c in &mut owned[start + range.0..start + range.1] {
+                *c = b'*';
+            }
+        }
+
+        start = end + 1;
+    }
+
+    data
+}
+
+fn is_continuation(line: &[u8]) -> bool {
+    matches!(line.first(), Some(b' ' | b'\t'))
+}
+
+fn line_end(line: &[u8]) -> usize {
+    if line.ends_with(b"\r") {
+        line.len() - 1
+    } else {
+        line.len()
+    }
+}
+
+/// The `(start, end)` of the value to mask, if the line is a redacted header.
+fn redacted_value(line: &[u8], names: &[HeaderName]) -> Option<(usize, usize)> {
+    let colon = line.iter().position(|c| *c == b':')?;
+    let name = &line[..colon];
+
+    let is_redacted = names
+        .iter()
+        .any(|n| n.as_str().as_bytes().eq_ignore_ascii_case(name));
+
+    if !is_redacted {
+        return None;
+    }
+
+    let end = line_end(line);
+
+    // Keep the whitespace after the colon.
+    let value = &line[colon + 1..end];
+
+    Some((colon + 1 + leading_ows(value), end))
+}
+
+/// The `(start, end)` of the value in a continuation line.
+fn continued_value(line: &[u8]) -> (usize, usize) {
+    let end = line_end(line);
+    (leading_ows(&line[..end]), end)
+}
+
+fn leading_ows(value: &[u8]) -> usize {
+    value
+        .iter()
+        .position(|c| *c != b' ' && *c != b'\t')
+        .unwrap_or(value.len())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn redact_str(data: &str) -> String {
+        let redacted = RedactedHeaders::default();
+        String::from_utf8(redacted.redact(data.as_bytes()).to_vec()).unwrap()
+    }
+
+    #[test]
+    fn test_redact() {
+        assert_eq!(
+            redact_str("GET / HTTP/1.1\r\nhost: q.test\r\nAuthorization: Bearer x\r\n\r\n"),
+            "GET / HTTP/1.1\r\nhost: q.test\r\nAuthorization: ********\r\n\r\n"
+        );
+
+        assert_eq!(
+            redact_str("cookie: a=1\r\nproxy-authorization:secret"),
+            "cookie: ***\r\nproxy-authorization:******"
+        );
+
+        // Not at the start of a line.
+        assert_eq!(redact_str("x-a: cookie: 1\r\n"), "x-a: cookie: 1\r\n");
+
+        let custom = RedactedHeaders::new(vec![HeaderName::from_static("x-api-key")]);
+        let custom = custom.redact(b"X-API-Key: 1\r\ncookie: 2\r\n");
+        assert_eq!(&*custom, b"X-API-Key: *\r\ncookie: 2\r\n");
+    }
+
+    #[test]
+    fn test_redact_folded() {
+        assert_eq!(
+            redact_str("set-cookie: a=1;\r\n  b=2\r\n\tc=3\r\nx-a: 1\r\n  2\r\n\r\n"),
+            "set-cookie: ****\r\n  ***\r\n\t***\r\nx-a: 1\r\n  2\r\n\r\n"
+        );
+    }
+
+    #[test]
+    fn test_redact_borrowed_when_unchanged() {
+        let data = b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n";
+        assert!(matches!(
+            RedactedHeaders::default().redact(data),
+            Cow::Borrowed(_)
+        ));
+    }
+}
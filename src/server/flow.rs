@@ -0,0 +1,756 @@
+//! A single request-response on a server connection.
+
+use std::fmt;
+use std::io::Write;
+use std::marker::PhantomData;
+
+use http::{HeaderMap, HeaderName, HeaderValue, Method, Request, Response, StatusCode, Version};
+
+use crate::body::{calculate_max_input, check_announced_trailers, BodyReader, BodyWriter};
+use crate::ext::HeaderIterExt;
+use crate::parser::{try_parse_request_with, HeadScanner};
+use crate::redact::RedactedHeaders;
+use crate::util::{compare_lowercase_ascii, do_write_headers, log_data, ArrayVec, Writer};
+use crate::{BodyMode, ChunkExtension, Error, HeaderCasing, Limits, ParsePolicy};
+
+#[doc(hidden)]
+pub mod state {
+    pub(crate) trait Named {
+        fn name() -> &'static str;
+    }
+
+    macro_rules! flow_state {
+        ($n:tt) => {
+            #[doc(hidden)]
+            pub struct $n(());
+            impl Named for $n {
+                fn name() -> &'static str {
+                    stringify!($n)
+                }
+            }
+        };
+    }
+
+    flow_state!(RecvRequest);
+    flow_state!(RecvBody);
+    flow_state!(SendResponse);
+    flow_state!(SendBody);
+    flow_state!(Cleanup);
+}
+use self::state::*;
+
+/// A server flow, in some state following the flow [state graph][crate::server]
+pub struct Flow<State> {
+    inner: Inner,
+    _ph: PhantomData<State>,
+}
+
+// pub(crate) for tests to inspect state
+#[derive(Debug)]
+pub(crate) struct Inner {
+    pub close_reason: ArrayVec<CloseReason, 4>,
+    pub method: Option<Method>,
+    pub version: Option<Version>,
+    pub reader: Option<BodyReader>,
+    pub stop_on_chunk_boundary: bool,
+    pub response: Option<Response<()>>,
+    pub writer: BodyWriter,
+    pub phase: Phase,
+    pub policy: ParsePolicy,
+    pub limits: Limits,
+    pub scanner: HeadScanner,
+    pub redacted: RedactedHeaders,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Phase {
+    StatusLine,
+    Headers(usize),
+    Body,
+}
+
+impl Phase {
+    fn is_prelude(&self) -> bool {
+        matches!(self, Phase::StatusLine | Phase::Headers(_))
+    }
+}
+
+/// Reasons for an ended flow that requires the connection to be closed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CloseReason {
+    /// HTTP/1.0 requires each request-response to end with a close.
+    Http10,
+
+    /// Client sent `connection: close`.
+    ClientConnectionClose,
+
+    /// Server sent `connection: close`.
+    ServerConnectionClose,
+
+    /// Response body is close delimited.
+    ///
+    /// The response has no `content-length` and we can't use chunked
+    /// transfer encoding towards an HTTP/1.0 client. The end of the body
+    /// is signaled by closing the connection.
+    CloseDelimitedBody,
+}
+
+impl CloseReason {
+    fn explain(&self) -> &'static str {
+        match self {
+            CloseReason::Http10 => "version is http1.0",
+            CloseReason::ClientConnectionClose => "client sent Connection: close",
+            CloseReason::ServerConnectionClose => "server sent Connection: close",
+            CloseReason::CloseDelimitedBody => "response body is close delimited",
+        }
+    }
+}
+
+impl<S> Flow<S> {
+    fn wrap(inner: Inner) -> Flow<S>
+    where
+        S: Named,
+    {
+        let wrapped = Flow {
+            inner,
+            _ph: PhantomData,
+        };
+
+        debug!("{:?}", wrapped);
+
+        wrapped
+    }
+
+    #[cfg(test)]
+    pub(crate) fn inner(&self) -> &Inner {
+        &self.inner
+    }
+}
+
+// //////////////////////////////////////////////////////////////////////////////////////////// RECV REQUEST
+
+impl Flow<RecvRequest> {
+    /// Create a new Flow.
+    ///
+    /// One flow handles one request-response. For keep-alive connections, a new flow
+    /// is created for each request.
+    ///
+    /// The `policy` decides how strictly a chunked request body is parsed. The request
+    /// head is always parsed strictly, i.e. obsolete line folding is rejected.
+    pub fn new(policy: ParsePolicy) -> Self {
+        let inner = Inner {
+            close_reason: ArrayVec::from_fn(|_| CloseReason::Http10),
+            method: None,
+            version: None,
+            reader: None,
+            stop_on_chunk_boundary: false,
+            response: None,
+            writer: BodyWriter::new_none(),
+            phase: Phase::StatusLine,
+            policy,
+            limits: Limits::new(),
+            scanner: HeadScanner::default(),
+            redacted: RedactedHeaders::default(),
+        };
+
+        Flow::wrap(inner)
+    }
+
+    /// Set the limits on received headers.
+    ///
+    /// Defaults to [`Limits::new()`].
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.inner.limits = limits;
+    }
+
+    /// Set the headers with values masked in the trace logging.
+    ///
+    /// Defaults to `authorization`, `proxy-authorization`, `cookie` and `set-cookie`.
+    /// See the client [`Flow::set_redacted_headers()`](crate::client::flow::Flow::set_redacted_headers).
+    pub fn set_redacted_headers(&mut self, names: Vec<HeaderName>) {
+        self.inner.redacted = RedactedHeaders::new(names);
+    }
+
+    /// Try reading a request from the input.
+    ///
+    /// This requires the entire request, including all headers to be present in the input buffer.
+    /// Until then, call it again with the same buffer and more bytes appended. Only the appended
+    /// bytes are scanned for the end of the headers. A shorter `input` starts the scan over.
+    ///
+    /// This is a breaking change from earlier versions, which re-parsed the entire `input` on
+    /// every call.
+    ///
+    /// The `(usize, Option<Request()>)` is `(input amount consumed, request`).
+    pub fn try_request(&mut self, input: &[u8]) -> Result<(usize, Option<Request<()>>), Error> {
+        let limits = &self.inner.limits;
+
+        // Stop before buffering too much, even if the headers are not complete.
+        let head_end = match self.inner.scanner.scan(input, limits)? {
+            Some(v) => v,
+            // Not enough input for a full request yet
+            None => return Ok((0, None)),
+        };
+
+        let mut headers = vec![httparse::EMPTY_HEADER; limits.max_headers];
+
+        let parsed =
+            try_parse_request_with(&input[..head_end], &mut headers).map_err(|e| match e {
+                Error::HttpParseTooManyHeaders => Error::TooManyHeaders(limits.max_headers),
+                e => e,
+            })?;
+
+        let (input_used, request) = match parsed {
+            Some(v) => v,
+            None => return Ok((0, None)),
+        };
+
+        log_data(&input[..input_used], &self.inner.redacted);
+
+        let http10 = request.version() == Version::HTTP_10;
+
+        let reader = BodyReader::for_request(
+            http10,
+            request.headers(),
+            &self.inner.policy,
+            &self.inner.limits,
+        )?;
+
+        if http10 {
+            self.inner.close_reason.push(CloseReason::Http10);
+        }
+
+        if request.headers().iter().has("connection", "close") {
+            self.inner
+                .close_reason
+                .push(CloseReason::ClientConnectionClose);
+        }
+
+        self.inner.method = Some(request.method().clone());
+        self.inner.version = Some(request.version());
+        self.inner.reader = Some(reader);
+
+        Ok((input_used, Some(request)))
+    }
+
+    /// Tell if we have finished receiving the request.
+    pub fn can_proceed(&self) -> bool {
+        self.inner.reader.is_some()
+    }
+
+    /// Proceed to the next state.
+    ///
+    /// This returns `None` if we have not finished receiving the request. It is guaranteed that if
+    /// `can_proceed()` returns true, this will return `Some`.
+    ///
+    /// There is always a [`RecvBody`] state, even if the request has no body. In that case
+    /// the body is immediately finished.
+    pub fn proceed(self) -> Option<Flow<RecvBody>> {
+        if !self.can_proceed() {
+            return None;
+        }
+
+        Some(Flow::wrap(self.inner))
+    }
+}
+
+impl Default for Flow<RecvRequest> {
+    fn default() -> Self {
+        Self::new(ParsePolicy::default())
+    }
+}
+
+// //////////////////////////////////////////////////////////////////////////////////////////// RECV BODY
+
+impl Flow<RecvBody> {
+    /// Read the request body from `input` to `output`.
+    ///
+    /// Depending on request headers, we can be in `transfer-encoding: chunked` or not. If we are,
+    /// there will be less `output` bytes than `input`.
+    ///
+    /// The result `(usize, usize)` is `(input consumed, output buffer used)`.
+    pub fn read(&mut self, input: &[u8], output: &mut [u8]) -> Result<(usize, usize), Error> {
+        let reader = self.inner.reader.as_mut().unwrap();
+
+        if reader.is_ended() {
+            return Ok((0, 0));
+        }
+
+        reader.read(
+            input,
+            output,
+            self.inner.stop_on_chunk_boundary,
+            &self.inner.redacted,
+        )
+    }
+
+    /// Set if we are stopping on chunk boundaries.
+    ///
+    /// If `false`, we try to fill entire `output` on each read() call.
+    /// Has no meaning unless the request in chunked.
+    ///
+    /// Defaults to `false`
+    pub fn stop_on_chunk_boundary(&mut self, enabled: bool) {
+        self.inner.stop_on_chunk_boundary = enabled;
+    }
+
+    /// Tell if the reading is on a chunk boundary.
+    ///
+    /// Used when we want to read exactly chunk-by-chunk.
+    ///
+    /// Only releveant if we first enabled `stop_on_chunk_boundary()`.
+    pub fn is_on_chunk_boundary(&self) -> bool {
+        self.inner.reader.as_ref().unwrap().is_on_chunk_boundary()
+    }
+
+    /// Extension parameters of the request body chunk being read.
+    ///
+    /// See the client [`Flow::chunk_extensions()`](crate::client::flow::Flow::chunk_extensions).
+    pub fn chunk_extensions(&self) -> &[ChunkExtension] {
+        self.inner.reader.as_ref().unwrap().chunk_extensions()
+    }
+
+    /// Tell which kind of mode the request body is.
+    pub fn body_mode(&self) -> BodyMode {
+        self.inner.reader.as_ref().unwrap().body_mode()
+    }
+
+    /// Trailer fields sent after the request body.
+    ///
+    /// Trailers are only possible for `transfer-encoding: chunked`, where they follow
+    /// the last chunk. Returns `None` until the body is fully read, or if the body is
+    /// not chunked.
+    pub fn trailers(&self) -> Option<&HeaderMap> {
+        self.inner.reader.as_ref().unwrap().trailers()
+    }
+
+    /// Signal that the transport hit EOF, i.e. the client closed the connection.
+    ///
+    /// Errors with [`Error::IncompleteBody`] if the request body isn't fully read.
+    pub fn signal_eof(&mut self) -> Result<(), Error> {
+        self.inner.reader.as_mut().unwrap().signal_eof()
+    }
+
+    /// Check if the request body has been fully received.
+    pub fn can_proceed(&self) -> bool {
+        self.inner.reader.as_ref().unwrap().is_ended()
+    }
+
+    /// Proceed to sending the response.
+    ///
+    /// The `response` is analyzed to figure out how to send the response body. A
+    /// `content-length` header means a sized body, otherwise we use `transfer-encoding: chunked`
+    /// (or a close delimited body for HTTP/1.0 clients). Responses to `HEAD`, and `1xx`, `204`
+    /// and `304` responses never have a body.
+    ///
+    /// Returns `None` if we have not fully received the request body. It is guaranteed that if
+    /// `can_proceed()` returns `true`, this will return `Some`.
+    pub fn proceed(mut self, response: Response<()>) -> Result<Option<Flow<SendResponse>>, Error> {
+        if !self.can_proceed() {
+            return Ok(None);
+        }
+
+        self.inner.analyze_response(response)?;
+
+        Ok(Some(Flow::wrap(self.inner)))
+    }
+}
+
+impl Inner {
+    fn analyze_response(&mut self, mut response: Response<()>) -> Result<(), Error> {
+        // unwrap is ok because we can't be here without having read a request.
+        let method = self.method.as_ref().unwrap();
+        let http10 = self.version == Some(Version::HTTP_10);
+        let status = response.status();
+
+        let headers = response.headers();
+
+        if headers.get_all("content-length").iter().count() > 1 {
+            return Err(Error::TooManyContentLengthHeaders);
+        }
+
+        let mut content_length: Option<u64> = None;
+        if let Some(h) = headers.get("content-length") {
+            let n = h
+                .to_str()
+                .ok()
+                .and_then(|s| s.parse::<u64>().ok())
+                .ok_or(Error::BadContentLengthHeader)?;
+            content_length = Some(n);
+        }
+
+        let has_chunked = headers
+            .get_all("transfer-encoding")
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .any(|v| compare_lowercase_ascii(v.trim(), "chunked"));
+
+        let has_no_body =
+            // Responses to HEAD never have a body, even if content-length is set.
+            *method == Method::HEAD ||
+            // A successful response to CONNECT turns the connection into a tunnel.
+            status.is_success() && *method == Method::CONNECT ||
+            // All 1xx (informational), 204 (no content), and 304 (not modified) responses
+            // MUST NOT include a message-body.
+            status.is_informational() ||
+            matches!(status, StatusCode::NO_CONTENT | StatusCode::NOT_MODIFIED);
+
+        let writer = if has_no_body {
+            BodyWriter::new_none()
+        } else if has_chunked && !http10 {
+            // chunked "wins" over content-length
+            BodyWriter::new_chunked()
+        } else if let Some(n) = content_length {
+            BodyWriter::new_sized(n)
+        } else if http10 {
+            // HTTP/1.0 clients don't understand chunked, so the only way to end the body is
+            // to close the connection.
+            self.close_reason.push(CloseReason::CloseDelimitedBody);
+            BodyWriter::new_close_delimited()
+        } else {
+            // User did not set a body header, we set one.
+            let writer = BodyWriter::new_chunked();
+            let header = writer.body_header()?;
+            response.headers_mut().insert(header.0, header.1);
+            writer
+        };
+
+        if has_chunked {
+            if http10 {
+                // HTTP/1.0 clients don't understand transfer-encoding, and the body is not chunked.
+                response.headers_mut().remove("transfer-encoding");
+            } else if content_length.is_some() {
+                // RFC 9112 6.2: a sender must not send content-length with transfer-encoding.
+                response.headers_mut().remove("content-length");
+            }
+        }
+
+        if response.headers().iter().has("connection", "close") {
+            self.close_reason.push(CloseReason::ServerConnectionClose);
+        } else if !self.close_reason.is_empty() && !http10 {
+            // Tell the client we are going to close the connection.
+            response
+                .headers_mut()
+                .insert("connection", HeaderValue::from_static("close"));
+        }
+
+        self.writer = writer;
+        self.response = Some(response);
+
+        Ok(())
+    }
+
+    fn has_response_body(&self) -> bool {
+        self.writer.has_body() && self.writer.left_to_send() != Some(0)
+    }
+}
+
+// //////////////////////////////////////////////////////////////////////////////////////////// SEND RESPONSE
+
+impl Flow<SendResponse> {
+    /// Write the response to the buffer.
+    ///
+    /// Writes incrementally, it can be called repeatedly in situations where the output
+    /// buffer is small.
+    ///
+    /// This includes the status line, i.e. `HTTP/1.1 200 OK` and all headers.
+    /// The output buffer needs to be large enough for the longest row.
+    ///
+    /// If the output is too small for the longest line, the result is an `OutputOverflow` error.
+    ///
+    /// The `Ok(usize)` is the number of bytes of the `output` buffer that was used.
+    pub fn write(&mut self, output: &mut [u8]) -> Result<usize, Error> {
+        // unwrap is ok because we can't be here without a response.
+        let response = self.inner.response.as_ref().unwrap();
+
+        let mut w = Writer::new(output, &self.inner.redacted);
+        try_write_prelude(response, &mut self.inner.phase, &mut w)?;
+
+        let output_used = w.len();
+
+        Ok(output_used)
+    }
+
+    /// The response status.
+    pub fn status(&self) -> StatusCode {
+        self.inner.response.as_ref().unwrap().status()
+    }
+
+    /// Check whether the entire response has been sent.
+    ///
+    /// This is useful when the output buffer is small and we need to repeatedly
+    /// call `write()` to send the entire response.
+    pub fn can_proceed(&self) -> bool {
+        !self.inner.phase.is_prelude()
+    }
+
+    /// Attempt to proceed from this state to the next.
+    ///
+    /// Returns `None` if the entire response has not been sent. It is guaranteed that if
+    /// `can_proceed()` returns `true`, this will return `Some`.
+    pub fn proceed(self) -> Option<SendResponseResult> {
+        if !self.can_proceed() {
+            return None;
+        }
+
+        Some(if self.inner.has_response_body() {
+            SendResponseResult::SendBody(Flow::wrap(self.inner))
+        } else {
+            SendResponseResult::Cleanup(Flow::wrap(self.inner))
+        })
+    }
+}
+
+/// Resulting states from sending a response.
+///
+/// See [state graph][crate::server].
+pub enum SendResponseResult {
+    /// Send the response body.
+    SendBody(Flow<SendBody>),
+
+    /// Run cleanup.
+    Cleanup(Flow<Cleanup>),
+}
+
+fn try_write_prelude(
+    response: &Response<()>,
+    phase: &mut Phase,
+    w: &mut Writer,
+) -> Result<(), Error> {
+    let at_start = w.len();
+
+    loop {
+        if try_write_prelude_part(response, phase, w) {
+            continue;
+        }
+
+        let written = w.len() - at_start;
+
+        if written > 0 || !phase.is_prelude() {
+            return Ok(());
+        } else {
+            return Err(Error::OutputOverflow);
+        }
+    }
+}
+
+fn try_write_prelude_part(response: &Response<()>, phase: &mut Phase, w: &mut Writer) -> bool {
+    match phase {
+        Phase::StatusLine => {
+            let success = do_write_status_line(response, w);
+            if success {
+                *phase = Phase::Headers(0);
+            }
+            success
+        }
+
+        Phase::Headers(index) => {
+            let header_count = response.headers().len();
+
+            if header_count == 0 {
+                // No headers, only the empty line ending the prelude.
+                let success = w.try_write(|w| write!(w, "\r\n"));
+                if success {
+                    *phase = Phase::Body;
+                }
+                return false;
+            }
+
+            let skipped = response.headers().iter().skip(*index);
+
+            do_write_headers(
+                skipped,
+                index,
+                header_count - 1,
+                &HeaderCasing::Lowercase,
+                w,
+            );
+
+            if *index == header_count {
+                *phase = Phase::Body;
+            }
+            false
+        }
+
+        // We're past the header.
+        _ => false,
+    }
+}
+
+fn do_write_status_line(response: &Response<()>, w: &mut Writer) -> bool {
+    let status = response.status();
+    w.try_write(|w| {
+        write!(
+            w,
+            "{:?} {} {}\r\n",
+            response.version(),
+            status.as_u16(),
+            status.canonical_reason().unwrap_or("")
+        )
+    })
+}
+
+// //////////////////////////////////////////////////////////////////////////////////////////// SEND BODY
+
+impl Flow<SendBody> {
+    /// Write response body from `input` to `output`.
+    ///
+    /// This is called repeatedly until the entire body has been sent. The output buffer is filled
+    /// as much as possible for each call.
+    ///
+    /// Depending on response headers, the output might be `transfer-encoding: chunked`. Chunking means
+    /// the output is slightly larger than the input due to the extra length headers per chunk.
+    /// When not doing chunked, the input/output will be the same per call.
+    ///
+    /// The result `(usize, usize)` is `(input consumed, output used)`.
+    ///
+    /// **Important**
+    ///
+    /// To indicate that the body is fully sent, you call write with an `input` parameter set to `&[]`.
+    /// This ends the `transfer-encoding: chunked` and ensures the state is correct to proceed.
+    pub fn write(&mut self, input: &[u8], output: &mut [u8]) -> Result<(usize, usize), Error> {
+        let writer = &mut self.inner.writer;
+
+        if !input.is_empty() && writer.is_ended() {
+            return Err(Error::BodyContentAfterFinish);
+        }
+
+        if let Some(left) = writer.left_to_send() {
+            if input.len() as u64 > left {
+                return Err(Error::BodyLargerThanContentLength);
+            }
+        }
+
+        let mut w = Writer::new(output, &self.inner.redacted);
+        let input_used = writer.write(input, &mut w);
+
+        Ok((input_used, w.len()))
+    }
+
+    /// End a chunked response body with trailer fields.
+    ///
+    /// This is used instead of calling `write()` with an empty input to end the body.
+    /// Only possible when the body is `transfer-encoding: chunked`.
+    ///
+    /// Every field in `trailers` must be announced up front in a `trailer` response header,
+    /// or this errors with [`Error::TrailerNotAnnounced`].
+    ///
+    /// The ending is written in one go. The result is how much of the `output` that was used.
+    pub fn write_trailers(
+        &mut self,
+        trailers: &HeaderMap,
+        output: &mut [u8],
+    ) -> Result<usize, Error> {
+        let writer = &mut self.inner.writer;
+
+        if !writer.is_chunked() {
+            return Err(Error::BodyNotChunked);
+        }
+
+        if writer.is_ended() {
+            return Err(Error::BodyContentAfterFinish);
+        }
+
+        // unwrap is ok because we can't be here without a response.
+        let response = self.inner.response.as_ref().unwrap();
+        let announced = response.headers().get_all("trailer").iter();
+        check_announced_trailers(announced, trailers)?;
+
+        let mut w = Writer::new(output, &self.inner.redacted);
+
+        if !writer.write_trailers(trailers, &mut w) {
+            return Err(Error::OutputOverflow);
+        }
+
+        Ok(w.len())
+    }
+
+    /// Helper to avoid copying memory.
+    ///
+    /// When the transfer is _NOT_ chunked, `write()` just copies the `input` to the `output`.
+    /// This memcopy might be possible to avoid if the user can use the `input` buffer directly
+    /// against the transport.
+    ///
+    /// This function is used to "report" how much of the input that has been used. It's effectively
+    /// the same as the first `usize` in the pair returned by `write()`.
+    pub fn consume_direct_write(&mut self, amount: usize) -> Result<(), Error> {
+        let writer = &mut self.inner.writer;
+
+        if writer.is_chunked() {
+            return Err(Error::BodyIsChunked);
+        }
+
+        if let Some(left) = writer.left_to_send() {
+            if amount as u64 > left {
+                return Err(Error::BodyLargerThanContentLength);
+            }
+        }
+
+        writer.consume_direct_write(amount);
+
+        Ok(())
+    }
+
+    /// Calculate the max amount of input we can transfer to fill the `output_len`.
+    ///
+    /// For chunked transfer, the input is less than the output.
+    pub fn calculate_max_input(&self, output_len: usize) -> usize {
+        // For non-chunked, the entire output can be used.
+        if !self.is_chunked() {
+            return output_len;
+        }
+
+        calculate_max_input(output_len)
+    }
+
+    /// Test if the response body is chunked.
+    pub fn is_chunked(&self) -> bool {
+        self.inner.writer.is_chunked()
+    }
+
+    /// Check whether the response body is fully sent.
+    ///
+    /// For responses with a `content-length` header set, this will only become `true` once the
+    /// number of bytes communicated have been sent. For chunked transfer, this becomes `true`
+    /// after calling `write()` with an input of `&[]`.
+    pub fn can_proceed(&self) -> bool {
+        self.inner.writer.is_ended()
+    }
+
+    /// Proceed to the next state.
+    ///
+    /// Returns `None` if it's not possible to proceed. It's guaranteed that if `can_proceed()` returns
+    /// `true`, this will result in `Some`.
+    pub fn proceed(self) -> Option<Flow<Cleanup>> {
+        if !self.can_proceed() {
+            return None;
+        }
+
+        Some(Flow::wrap(self.inner))
+    }
+}
+
+// //////////////////////////////////////////////////////////////////////////////////////////// CLEANUP
+
+impl Flow<Cleanup> {
+    /// Tell if we must close the connection.
+    ///
+    /// If this is `false`, the connection can be kept alive and a new [`Flow`] used to
+    /// receive the next request.
+    pub fn must_close_connection(&self) -> bool {
+        self.close_reason().is_some()
+    }
+
+    /// If we are closing the connection, give a reason.
+    pub fn close_reason(&self) -> Option<&'static str> {
+        self.inner.close_reason.first().map(|s| s.explain())
+    }
+}
+
+// ////////////////////////////////////////////////////////////////////////////////////////////
+
+impl<State: Named> fmt::Debug for Flow<State> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Flow<{}>", State::name())
+    }
+}
@@ -0,0 +1,138 @@
+//! HTTP/1.1 server protocol
+//!
+//! Sans-IO protocol impl, which means "writing" and "reading" are made via buffers
+//! rather than the Write/Read std traits.
+//!
+//! The [`Flow`](flow::Flow) object mirrors the [client flow](crate::client) and attempts
+//! to encode correct HTTP/1.1 handling using state variables, for example
+//! `Flow<RecvRequest>` to represent the lifecycle stage where we are to receive a request.
+//!
+//! The states are:
+//!
+//! * **RecvRequest** - Receive the request, meaning the method, path and version
+//!   as well as the request headers
+//! * **RecvBody** - Receive the request body. This state is entered even if the
+//!   request has no body.
+//! * **SendResponse** - Send the status line and response headers
+//! * **SendBody** - Send the response body
+//! * **Cleanup** - Keep the connection alive for the next request, or close it
+//!
+//! ```text
+//!     ┌──────────────────┐
+//!     │   RecvRequest    │
+//!     └──────────────────┘
+//!               │
+//!               ▼
+//!     ┌──────────────────┐
+//!     │     RecvBody     │
+//!     └──────────────────┘
+//!               │
+//!               ▼
+//!     ┌──────────────────┐
+//!     │   SendResponse   │──┐
+//!     └──────────────────┘  │
+//!               │           │
+//!               ▼           │
+//!     ┌──────────────────┐  │
+//!     │     SendBody     │  │
+//!     └──────────────────┘  │
+//!               │           │
+//!               ▼           │
+//!     ┌──────────────────┐  │
+//!     │     Cleanup      │◀─┘
+//!     └──────────────────┘
+//! ```
+//!
+//! # Example
+//!
+//! ```
+//! use ureq_proto::server::flow::*;
+//! use ureq_proto::http::Response;
+//! use ureq_proto::ParsePolicy;
+//!
+//! // ********************************** RecvRequest
+//!
+//! let mut flow = Flow::new(ParsePolicy::default());
+//!
+//! let input = b"POST /my-path HTTP/1.1\r\n\
+//!     host: example.test\r\n\
+//!     content-length: 5\r\n\
+//!     \r\n\
+//!     hello";
+//!
+//! // try_request can be used repeatedly until we
+//! // get enough content including all headers.
+//! let (input_used, maybe_request) = flow.try_request(input).unwrap();
+//!
+//! assert_eq!(input_used, 65);
+//! let request = maybe_request.unwrap();
+//! assert_eq!(request.uri(), "/my-path");
+//!
+//! // ********************************** RecvBody
+//!
+//! let mut flow = flow.proceed().unwrap();
+//!
+//! let mut output = vec![0_u8; 1024];
+//!
+//! let (i, o) = flow.read(&input[input_used..], &mut output).unwrap();
+//! assert_eq!((i, o), (5, 5));
+//! assert_eq!(&output[..o], b"hello");
+//!
+//! // ********************************** SendResponse
+//!
+//! let response = Response::builder()
+//!     .header("content-type", "text/plain")
+//!     .body(())
+//!     .unwrap();
+//!
+//! let mut flow = flow.proceed(response).unwrap().unwrap();
+//!
+//! let output_used = flow.write(&mut output).unwrap();
+//!
+//! // Without a content-length, the body is chunked.
+//! assert_eq!(&output[..output_used], b"\
+//!     HTTP/1.1 200 OK\r\n\
+//!     content-type: text/plain\r\n\
+//!     transfer-encoding: chunked\r\n\
+//!     \r\n");
+//!
+//! // ********************************** SendBody
+//!
+//! let mut flow = match flow.proceed() {
+//!     Some(SendResponseResult::SendBody(v)) => v,
+//!     _ => panic!(),
+//! };
+//!
+//! let (_, o1) = flow.write(b"hi there!", &mut output).unwrap();
+//!
+//! // End the body with an empty input.
+//! let (_, o2) = flow.write(&[], &mut output[o1..]).unwrap();
+//!
+//! assert_eq!(&output[..o1 + o2], b"9\r\nhi there!\r\n0\r\n\r\n");
+//!
+//! // ********************************** Cleanup
+//!
+//! let flow = flow.proceed().unwrap();
+//!
+//! if flow.must_close_connection() {
+//!     // connection.close();
+//! } else {
+//!     // Read the next request with a new Flow.
+//! }
+//! ```
+//!
+//! # Out of scope:
+//!
+//! * Opening/closing sockets
+//! * TLS (https)
+//! * Routing
+//! * Body data transformations (charset, compression etc)
+//!
+
+pub mod flow;
+
+#[cfg(test)]
+mod test;
+
+/// Max number of headers to parse from an HTTP request
+pub const MAX_REQUEST_HEADERS: usize = 128;
@@ -0,0 +1,52 @@
+use http::Response;
+
+use crate::server::flow::state::{Cleanup, RecvBody, SendResponse};
+use crate::server::flow::{Flow, SendResponseResult};
+use crate::ParsePolicy;
+
+mod state_recv_request;
+
+mod state_recv_body;
+
+mod state_send_response;
+
+mod state_send_body;
+
+mod state_cleanup;
+
+trait TestSliceExt {
+    fn as_str(&self) -> &str;
+}
+
+impl TestSliceExt for [u8] {
+    fn as_str(&self) -> &str {
+        std::str::from_utf8(self).unwrap()
+    }
+}
+
+// The unwraps in these helpers are ok because the tests are not supposed
+// to test the helpers themselves.
+
+fn to_recv_body(request: &[u8]) -> Flow<RecvBody> {
+    let mut flow = Flow::new(ParsePolicy::default());
+    flow.try_request(request).unwrap();
+    flow.proceed().unwrap()
+}
+
+fn to_send_response(request: &[u8], response: Response<()>) -> Flow<SendResponse> {
+    let flow = to_recv_body(request);
+    flow.proceed(response).unwrap().unwrap()
+}
+
+fn to_cleanup(request: &[u8], response: Response<()>) -> Flow<Cleanup> {
+    let mut flow = to_send_response(request, response);
+    flow.write(&mut vec![0; 1024]).unwrap();
+
+    match flow.proceed().unwrap() {
+        SendResponseResult::SendBody(mut flow) => {
+            flow.write(&[], &mut vec![0; 1024]).unwrap();
+            flow.proceed().unwrap()
+        }
+        SendResponseResult::Cleanup(flow) => flow,
+    }
+}
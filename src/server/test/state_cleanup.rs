@@ -0,0 +1,65 @@
+use http::Response;
+
+use crate::server::flow::CloseReason;
+
+use super::TestSliceExt;
+use super::{to_cleanup, to_send_response};
+
+#[test]
+fn reuse_keep_alive() {
+    let flow = to_cleanup(b"GET / HTTP/1.1\r\n\r\n", Response::new(()));
+
+    assert!(!flow.must_close_connection());
+}
+
+#[test]
+fn close_due_to_http10() {
+    let flow = to_cleanup(b"GET / HTTP/1.0\r\n\r\n", Response::new(()));
+
+    let inner = flow.inner();
+    assert_eq!(*inner.close_reason.first().unwrap(), CloseReason::Http10);
+    assert!(inner
+        .close_reason
+        .contains(&CloseReason::CloseDelimitedBody));
+
+    assert!(flow.must_close_connection());
+}
+
+#[test]
+fn close_due_to_client_connection_close() {
+    let request = b"GET / HTTP/1.1\r\nconnection: close\r\n\r\n";
+
+    // The response tells the client we are closing.
+    let mut flow = to_send_response(request, Response::new(()));
+    let mut output = vec![0; 1024];
+    let n = flow.write(&mut output).unwrap();
+    assert!(output[..n].as_str().contains("connection: close\r\n"));
+
+    let flow = to_cleanup(request, Response::new(()));
+
+    let inner = flow.inner();
+    assert_eq!(
+        *inner.close_reason.first().unwrap(),
+        CloseReason::ClientConnectionClose
+    );
+
+    assert!(flow.must_close_connection());
+}
+
+#[test]
+fn close_due_to_server_connection_close() {
+    let response = Response::builder()
+        .header("connection", "close")
+        .body(())
+        .unwrap();
+
+    let flow = to_cleanup(b"GET / HTTP/1.1\r\n\r\n", response);
+
+    let inner = flow.inner();
+    assert_eq!(
+        *inner.close_reason.first().unwrap(),
+        CloseReason::ServerConnectionClose
+    );
+
+    assert!(flow.must_close_connection());
+}
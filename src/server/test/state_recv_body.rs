@@ -0,0 +1,69 @@
+use http::Response;
+
+use crate::{BodyMode, Error};
+
+use super::to_recv_body;
+use super::TestSliceExt;
+
+#[test]
+fn recv_body_content_length() {
+    let mut flow = to_recv_body(b"POST / HTTP/1.1\r\ncontent-length: 5\r\n\r\n");
+
+    assert_eq!(flow.body_mode(), BodyMode::LengthDelimited(5));
+
+    let mut output = vec![0; 1024];
+
+    let (input_used, output_used) = flow.read(b"hel", &mut output).unwrap();
+    assert_eq!(input_used, 3);
+    assert_eq!(output_used, 3);
+    assert_eq!(output[..output_used].as_str(), "hel");
+    assert!(!flow.can_proceed());
+
+    let (input_used, output_used) = flow.read(b"lo", &mut output).unwrap();
+    assert_eq!(input_used, 2);
+    assert_eq!(output_used, 2);
+    assert_eq!(output[..output_used].as_str(), "lo");
+    assert!(flow.can_proceed());
+}
+
+#[test]
+fn recv_body_chunked() {
+    let mut flow = to_recv_body(b"POST / HTTP/1.1\r\ntransfer-encoding: chunked\r\n\r\n");
+
+    assert_eq!(flow.body_mode(), BodyMode::Chunked);
+
+    let mut output = vec![0; 1024];
+
+    let (input_used, output_used) = flow.read(b"5\r\nhello\r\n0\r\n\r\n", &mut output).unwrap();
+    assert_eq!(input_used, 15);
+    assert_eq!(output_used, 5);
+    assert_eq!(output[..output_used].as_str(), "hello");
+    assert!(flow.can_proceed());
+}
+
+#[test]
+fn post_without_body_headers() {
+    // Request bodies are never close delimited.
+    let flow = to_recv_body(b"POST / HTTP/1.1\r\n\r\n");
+
+    assert_eq!(flow.body_mode(), BodyMode::NoBody);
+    assert!(flow.can_proceed());
+}
+
+#[test]
+fn proceed_before_body_read() {
+    let flow = to_recv_body(b"POST / HTTP/1.1\r\ncontent-length: 5\r\n\r\n");
+
+    assert!(!flow.can_proceed());
+    assert!(flow.proceed(Response::new(())).unwrap().is_none());
+}
+
+#[test]
+fn recv_body_eof_truncated() {
+    let mut flow = to_recv_body(b"POST / HTTP/1.1\r\ncontent-length: 5\r\n\r\n");
+
+    flow.read(b"hel", &mut [0; 1024]).unwrap();
+
+    assert_eq!(flow.signal_eof(), Err(Error::IncompleteBody));
+    assert!(!flow.can_proceed());
+}
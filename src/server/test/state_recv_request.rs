@@ -0,0 +1,118 @@
+use http::{Method, Version};
+
+use crate::server::flow::{CloseReason, Flow};
+use crate::{BodyMode, Error, Limits, ParsePolicy};
+
+const REQUEST: &[u8] = b"\
+        GET /page?q=1 HTTP/1.1\r\n\
+        Host: q.test\r\n\
+        Accept: */*\r\n\
+        \r\n";
+
+#[test]
+fn receive_incomplete_request() {
+    // -1 to never reach the end
+    for i in 0..REQUEST.len() - 1 {
+        let mut flow = Flow::new(ParsePolicy::default());
+
+        let (input_used, maybe_request) = flow.try_request(&REQUEST[..i]).unwrap();
+        assert_eq!(input_used, 0);
+        assert!(maybe_request.is_none());
+        assert!(!flow.can_proceed());
+    }
+}
+
+#[test]
+fn receive_complete_request() {
+    let mut flow = Flow::new(ParsePolicy::default());
+
+    let (input_used, maybe_request) = flow.try_request(REQUEST).unwrap();
+    assert_eq!(input_used, 53);
+
+    let request = maybe_request.unwrap();
+
+    assert_eq!(request.method(), Method::GET);
+    assert_eq!(request.uri(), "/page?q=1");
+    assert_eq!(request.version(), Version::HTTP_11);
+    assert_eq!(request.headers().get("host").unwrap(), "q.test");
+
+    assert!(flow.can_proceed());
+
+    let flow = flow.proceed().unwrap();
+    assert_eq!(flow.body_mode(), BodyMode::NoBody);
+    assert!(flow.can_proceed());
+}
+
+#[test]
+fn receive_trickling_request() {
+    let mut flow = Flow::new(ParsePolicy::default());
+
+    // Same flow, growing input.
+    for i in 0..REQUEST.len() - 1 {
+        let (input_used, maybe_request) = flow.try_request(&REQUEST[..i]).unwrap();
+        assert_eq!(input_used, 0);
+        assert!(maybe_request.is_none());
+    }
+
+    let (input_used, maybe_request) = flow.try_request(REQUEST).unwrap();
+    assert_eq!(input_used, 53);
+    assert!(maybe_request.is_some());
+    assert!(flow.can_proceed());
+}
+
+#[test]
+fn request_http10() {
+    let mut flow = Flow::new(ParsePolicy::default());
+
+    flow.try_request(b"GET / HTTP/1.0\r\n\r\n").unwrap();
+
+    let inner = flow.inner();
+    assert_eq!(*inner.close_reason.first().unwrap(), CloseReason::Http10);
+}
+
+#[test]
+fn request_connection_close() {
+    let mut flow = Flow::new(ParsePolicy::default());
+
+    flow.try_request(b"GET / HTTP/1.1\r\nconnection: close\r\n\r\n")
+        .unwrap();
+
+    let inner = flow.inner();
+    assert_eq!(
+        *inner.close_reason.first().unwrap(),
+        CloseReason::ClientConnectionClose
+    );
+}
+
+#[test]
+fn content_length_and_transfer_encoding() {
+    let mut flow = Flow::new(ParsePolicy::default());
+
+    let err = flow
+        .try_request(b"POST / HTTP/1.1\r\ncontent-length: 5\r\ntransfer-encoding: chunked\r\n\r\n")
+        .unwrap_err();
+
+    assert_eq!(err, Error::ContentLengthAndTransferEncoding);
+}
+
+#[test]
+fn limits() {
+    let mut flow = Flow::new(ParsePolicy::default());
+    flow.set_limits(Limits::new().max_headers(1));
+
+    let err = flow.try_request(REQUEST).unwrap_err();
+    assert_eq!(err, Error::TooManyHeaders(1));
+
+    // Before the request is complete.
+    let mut flow = Flow::new(ParsePolicy::default());
+    flow.set_limits(Limits::new().max_header_size(40));
+
+    let err = flow.try_request(&REQUEST[..45]).unwrap_err();
+    assert_eq!(err, Error::HeadersTooLarge(40));
+
+    let mut flow = Flow::new(ParsePolicy::default());
+    flow.set_limits(Limits::new().max_line_len(10));
+
+    let err = flow.try_request(&REQUEST[..20]).unwrap_err();
+    assert_eq!(err, Error::HeaderLineTooLong(10));
+}
@@ -0,0 +1,106 @@
+use http::{HeaderMap, Response};
+
+use crate::server::flow::state::SendBody;
+use crate::server::flow::{Flow, SendResponseResult};
+use crate::Error;
+
+use super::to_send_response;
+use super::TestSliceExt;
+
+fn to_send_body(request: &[u8], response: Response<()>) -> Flow<SendBody> {
+    let mut flow = to_send_response(request, response);
+    flow.write(&mut vec![0; 1024]).unwrap();
+
+    match flow.proceed() {
+        Some(SendResponseResult::SendBody(v)) => v,
+        _ => unreachable!(),
+    }
+}
+
+#[test]
+fn send_body_chunked() {
+    let mut flow = to_send_body(b"GET / HTTP/1.1\r\n\r\n", Response::new(()));
+
+    assert!(flow.is_chunked());
+
+    let mut output = vec![0; 1024];
+
+    let (i1, n1) = flow.write(b"hallo", &mut output).unwrap();
+    assert_eq!(i1, 5);
+    assert!(!flow.can_proceed());
+
+    let (_, n2) = flow.write(&[], &mut output[n1..]).unwrap();
+    assert!(flow.can_proceed());
+
+    assert_eq!(output[..n1 + n2].as_str(), "5\r\nhallo\r\n0\r\n\r\n");
+}
+
+#[test]
+fn send_body_content_length() {
+    let response = Response::builder()
+        .header("content-length", "5")
+        .body(())
+        .unwrap();
+
+    let mut flow = to_send_body(b"GET / HTTP/1.1\r\n\r\n", response);
+
+    assert!(!flow.is_chunked());
+
+    let mut output = vec![0; 1024];
+
+    let (i, n) = flow.write(b"hallo", &mut output).unwrap();
+    assert_eq!((i, n), (5, 5));
+    assert_eq!(output[..n].as_str(), "hallo");
+    assert!(flow.can_proceed());
+
+    let err = flow.write(b"more", &mut output).unwrap_err();
+    assert_eq!(err, Error::BodyContentAfterFinish);
+}
+
+#[test]
+fn send_body_too_much() {
+    let response = Response::builder()
+        .header("content-length", "2")
+        .body(())
+        .unwrap();
+
+    let mut flow = to_send_body(b"GET / HTTP/1.1\r\n\r\n", response);
+
+    let err = flow.write(b"hallo", &mut vec![0; 1024]).unwrap_err();
+    assert_eq!(err, Error::BodyLargerThanContentLength);
+}
+
+#[test]
+fn send_body_close_delimited_http10() {
+    let mut flow = to_send_body(b"GET / HTTP/1.0\r\n\r\n", Response::new(()));
+
+    assert!(!flow.is_chunked());
+
+    let mut output = vec![0; 1024];
+
+    let (i, n) = flow.write(b"hallo", &mut output).unwrap();
+    assert_eq!((i, n), (5, 5));
+    assert!(!flow.can_proceed());
+
+    flow.write(&[], &mut output).unwrap();
+    assert!(flow.can_proceed());
+}
+
+#[test]
+fn send_body_chunked_trailers() {
+    let response = Response::builder()
+        .header("trailer", "grpc-status")
+        .body(())
+        .unwrap();
+
+    let mut flow = to_send_body(b"GET / HTTP/1.1\r\n\r\n", response);
+
+    let mut trailers = HeaderMap::new();
+    trailers.insert("grpc-status", "0".parse().unwrap());
+
+    let mut output = vec![0; 1024];
+    let n = flow.write_trailers(&trailers, &mut output).unwrap();
+
+    assert_eq!(output[..n].as_str(), "0\r\ngrpc-status: 0\r\n\r\n");
+    assert!(flow.can_proceed());
+}
@@ -0,0 +1,149 @@
+use http::{Response, StatusCode};
+
+use crate::server::flow::SendResponseResult;
+use crate::Error;
+
+use super::to_send_response;
+use super::TestSliceExt;
+
+#[test]
+fn write_response_chunked() {
+    let mut flow = to_send_response(b"GET / HTTP/1.1\r\n\r\n", Response::new(()));
+
+    assert!(!flow.can_proceed());
+
+    let mut output = vec![0; 1024];
+    let n = flow.write(&mut output).unwrap();
+
+    let cmp = "\
+        HTTP/1.1 200 OK\r\n\
+        transfer-encoding: chunked\r\n\
+        \r\n";
+
+    assert_eq!(output[..n].as_str(), cmp);
+    assert!(flow.can_proceed());
+
+    assert!(matches!(
+        flow.proceed(),
+        Some(SendResponseResult::SendBody(_))
+    ));
+}
+
+#[test]
+fn write_response_content_length() {
+    let response = Response::builder()
+        .status(StatusCode::NOT_FOUND)
+        .header("content-length", "5")
+        .body(())
+        .unwrap();
+
+    let mut flow = to_send_response(b"GET / HTTP/1.1\r\n\r\n", response);
+
+    let mut output = vec![0; 1024];
+    let n = flow.write(&mut output).unwrap();
+
+    let cmp = "\
+        HTTP/1.1 404 Not Found\r\n\
+        content-length: 5\r\n\
+        \r\n";
+
+    assert_eq!(output[..n].as_str(), cmp);
+}
+
+#[test]
+fn write_response_no_headers() {
+    let response = Response::builder()
+        .status(StatusCode::NO_CONTENT)
+        .body(())
+        .unwrap();
+
+    let mut flow = to_send_response(b"GET / HTTP/1.1\r\n\r\n", response);
+
+    let mut output = vec![0; 1024];
+    let n = flow.write(&mut output).unwrap();
+
+    assert_eq!(output[..n].as_str(), "HTTP/1.1 204 No Content\r\n\r\n");
+
+    assert!(matches!(
+        flow.proceed(),
+        Some(SendResponseResult::Cleanup(_))
+    ));
+}
+
+#[test]
+fn write_response_to_head() {
+    let response = Response::builder()
+        .header("content-length", "100")
+        .body(())
+        .unwrap();
+
+    let mut flow = to_send_response(b"HEAD / HTTP/1.1\r\n\r\n", response);
+
+    flow.write(&mut vec![0; 1024]).unwrap();
+
+    assert!(matches!(
+        flow.proceed(),
+        Some(SendResponseResult::Cleanup(_))
+    ));
+}
+
+#[test]
+fn write_response_small_output() {
+    let mut flow = to_send_response(b"GET / HTTP/1.1\r\n\r\n", Response::new(()));
+
+    let mut output = vec![0; 1024];
+
+    let n = flow.write(&mut output[..20]).unwrap();
+    assert_eq!(output[..n].as_str(), "HTTP/1.1 200 OK\r\n");
+    assert!(!flow.can_proceed());
+
+    let n = flow.write(&mut output[..40]).unwrap();
+    assert_eq!(output[..n].as_str(), "transfer-encoding: chunked\r\n\r\n");
+    assert!(flow.can_proceed());
+}
+
+#[test]
+fn short_buffer() {
+    let mut flow = to_send_response(b"GET / HTTP/1.1\r\n\r\n", Response::new(()));
+
+    let r = flow.write(&mut [0; 10]);
+
+    assert_eq!(r.unwrap_err(), Error::OutputOverflow);
+    assert!(!flow.can_proceed());
+}
+
+#[test]
+fn write_response_http10_drops_transfer_encoding() {
+    let response = Response::builder()
+        .header("transfer-encoding", "chunked")
+        .body(())
+        .unwrap();
+
+    let mut flow = to_send_response(b"GET / HTTP/1.0\r\n\r\n", response);
+
+    let mut output = vec![0; 1024];
+    let n = flow.write(&mut output).unwrap();
+
+    assert_eq!(output[..n].as_str(), "HTTP/1.1 200 OK\r\n\r\n");
+}
+
+#[test]
+fn write_response_transfer_encoding_drops_content_length() {
+    let response = Response::builder()
+        .header("content-length", "5")
+        .header("transfer-encoding", "chunked")
+        .body(())
+        .unwrap();
+
+    let mut flow = to_send_response(b"GET / HTTP/1.1\r\n\r\n", response);
+
+    let mut output = vec![0; 1024];
+    let n = flow.write(&mut output).unwrap();
+
+    let cmp = "\
+        HTTP/1.1 200 OK\r\n\
+        transfer-encoding: chunked\r\n\
+        \r\n";
+
+    assert_eq!(output[..n].as_str(), cmp);
+}
@@ -1,7 +1,12 @@
 use std::fmt;
-use std::io::{self, Cursor};
+use std::io::{self, Cursor, Write};
 use std::ops::{Deref, DerefMut};
 
+use http::{HeaderName, HeaderValue};
+
+use crate::redact::RedactedHeaders;
+use crate::HeaderCasing;
+
 pub(crate) fn find_crlf(b: &[u8]) -> Option<usize> {
     let cr = b.iter().position(|c| *c == b'\r')?;
     let maybe_lf = b.get(cr + 1)?;
@@ -30,11 +35,38 @@ pub(crate) fn compare_lowercase_ascii(a: &str, lowercased: &str) -> bool {
     true
 }
 
-pub(crate) struct Writer<'a>(pub Cursor<&'a mut [u8]>);
+/// Standard base64 with padding (RFC 4648 4).
+pub(crate) fn base64(input: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut out = String::with_capacity((input.len() + 2) / 3 * 4);
+
+    for chunk in input.chunks(3) {
+        let b = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                let idx = (n >> (18 - 6 * i)) & 0x3f;
+                out.push(ALPHABET[idx as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+
+    out
+}
+
+pub(crate) struct Writer<'a>(pub Cursor<&'a mut [u8]>, RedactedHeaders);
 
 impl<'a> Writer<'a> {
-    pub(crate) fn new(output: &'a mut [u8]) -> Writer<'a> {
-        Self(Cursor::new(output))
+    pub(crate) fn new(output: &'a mut [u8], redacted: &RedactedHeaders) -> Writer<'a> {
+        Self(Cursor::new(output), redacted.clone())
     }
 
     pub fn len(&self) -> usize {
@@ -70,11 +102,50 @@ const CHARS_PER_ROW: usize = 16;
 impl<'a> Drop for Writer<'a> {
     fn drop(&mut self) {
         let len = self.len();
-        log_data(&self.0.get_ref()[..len]);
+        log_data(&self.0.get_ref()[..len], &self.1);
+    }
+}
+
+/// Write as many of the `headers` as fit, advancing `index` for each written header.
+///
+/// The header at `last_index` is followed by the empty line ending the head.
+pub(crate) fn do_write_headers<'a, I>(
+    headers: I,
+    index: &mut usize,
+    last_index: usize,
+    casing: &HeaderCasing,
+    w: &mut Writer,
+) where
+    I: Iterator<Item = (&'a HeaderName, &'a HeaderValue)>,
+{
+    for h in headers {
+        let success = w.try_write(|w| {
+            casing.write_name(w, h.0)?;
+            write!(w, ": ")?;
+            w.write_all(h.1.as_bytes())?;
+            write!(w, "\r\n")?;
+            if *index == last_index {
+                write!(w, "\r\n")?;
+            }
+            Ok(())
+        });
+
+        if success {
+            *index += 1;
+        } else {
+            break;
+        }
     }
 }
 
-pub(crate) fn log_data(data: &[u8]) {
+pub(crate) fn log_data(data: &[u8], redacted: &RedactedHeaders) {
+    if !log_enabled!(log::Level::Trace) {
+        return;
+    }
+
+    // Header values such as authorization must never reach the logger.
+    let data = redacted.redact(data);
+
     for row in data.chunks(CHARS_PER_ROW) {
         trace!("{:?}", Row(row))
     }
@@ -195,3 +266,20 @@ impl<'a, T, const N: usize> IntoIterator for &'a ArrayVec<T, N> {
         self[..self.len].iter()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_base64() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foob"), "Zm9vYg==");
+        assert_eq!(base64(b"fooba"), "Zm9vYmE=");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+        assert_eq!(base64(&[0xff, 0xfe]), "//4=");
+    }
+}
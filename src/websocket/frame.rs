@@ -0,0 +1,958 @@
+use std::fmt;
+
+use crate::Error;
+
+/// Max payload length of a control frame (RFC 6455 5.5).
+const MAX_CONTROL_PAYLOAD: usize = 125;
+
+/// Max length of a frame header: 2 bytes + 8 bytes extended length + 4 bytes mask.
+const MAX_HEADER_LEN: usize = 14;
+
+/// Which end of the connection we are.
+///
+/// Frames sent from client to server are always masked, and frames from server to
+/// client never are (RFC 6455 5.1).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    /// The end that sent the opening handshake.
+    Client,
+    /// The end that responded to the opening handshake.
+    Server,
+}
+
+/// Kind of data message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageKind {
+    /// UTF-8 text.
+    Text,
+    /// Arbitrary binary data.
+    Binary,
+}
+
+/// Frame opcode (RFC 6455 5.2).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Opcode {
+    Continuation,
+    Text,
+    Binary,
+    Close,
+    Ping,
+    Pong,
+}
+
+impl Opcode {
+    fn from_u8(v: u8) -> Option<Opcode> {
+        Some(match v {
+            0x0 => Opcode::Continuation,
+            0x1 => Opcode::Text,
+            0x2 => Opcode::Binary,
+            0x8 => Opcode::Close,
+            0x9 => Opcode::Ping,
+            0xa => Opcode::Pong,
+            _ => return None,
+        })
+    }
+
+    fn as_u8(&self) -> u8 {
+        match self {
+            Opcode::Continuation => 0x0,
+            Opcode::Text => 0x1,
+            Opcode::Binary => 0x2,
+            Opcode::Close => 0x8,
+            Opcode::Ping => 0x9,
+            Opcode::Pong => 0xa,
+        }
+    }
+
+    fn is_control(&self) -> bool {
+        matches!(self, Opcode::Close | Opcode::Ping | Opcode::Pong)
+    }
+}
+
+impl From<MessageKind> for Opcode {
+    fn from(value: MessageKind) -> Self {
+        match value {
+            MessageKind::Text => Opcode::Text,
+            MessageKind::Binary => Opcode::Binary,
+        }
+    }
+}
+
+/// Status code sent in a close frame (RFC 6455 7.4).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CloseCode(u16);
+
+impl CloseCode {
+    /// 1000 Normal closure.
+    pub const NORMAL: CloseCode = CloseCode(1000);
+    /// 1001 Endpoint is going away.
+    pub const GOING_AWAY: CloseCode = CloseCode(1001);
+    /// 1002 Protocol error.
+    pub const PROTOCOL_ERROR: CloseCode = CloseCode(1002);
+    /// 1003 Received a data type that can't be accepted.
+    pub const UNSUPPORTED_DATA: CloseCode = CloseCode(1003);
+    /// 1007 Data inconsistent with the message type, such as non-UTF-8 text.
+    pub const INVALID_PAYLOAD: CloseCode = CloseCode(1007);
+    /// 1008 Message violates policy.
+    pub const POLICY_VIOLATION: CloseCode = CloseCode(1008);
+    /// 1009 Message too big to process.
+    pub const MESSAGE_TOO_BIG: CloseCode = CloseCode(1009);
+    /// 1010 Client expected the server to negotiate an extension.
+    pub const MANDATORY_EXTENSION: CloseCode = CloseCode(1010);
+    /// 1011 Unexpected condition on the server.
+    pub const INTERNAL_ERROR: CloseCode = CloseCode(1011);
+
+    /// Create a close code from a number.
+    ///
+    /// Returns `None` for codes that must not be sent in a close frame, such as
+    /// 1005 (no status) and 1006 (abnormal closure).
+    pub fn from_u16(code: u16) -> Option<CloseCode> {
+        match code {
+            1000..=1003 | 1007..=1014 | 3000..=4999 => Some(CloseCode(code)),
+            _ => None,
+        }
+    }
+
+    /// The numeric value of the code.
+    pub fn as_u16(&self) -> u16 {
+        self.0
+    }
+}
+
+impl fmt::Display for CloseCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// A control frame received by the [`FrameDecoder`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Control {
+    /// Ping with application data. Should be answered with a pong echoing the data.
+    Ping(Vec<u8>),
+    /// Pong with application data.
+    Pong(Vec<u8>),
+    /// Close with an optional code and reason.
+    ///
+    /// If we didn't initiate the close, it should be answered with a close frame.
+    Close {
+        /// Close code, `None` if the frame had no payload.
+        code: Option<CloseCode>,
+        /// UTF-8 reason for closing. Can be empty.
+        reason: String,
+    },
+}
+
+/// Encodes frames into an output buffer.
+///
+/// Data messages can be fragmented over several frames. Control frames (ping, pong,
+/// close) can be written in between the fragments of a data message.
+#[derive(Debug)]
+pub struct FrameEncoder {
+    role: Role,
+    message: Option<MessageKind>,
+    closed: bool,
+}
+
+impl FrameEncoder {
+    /// Create a new encoder.
+    pub fn new(role: Role) -> Self {
+        FrameEncoder {
+            role,
+            message: None,
+            closed: false,
+        }
+    }
+
+    /// Write data as one frame.
+    ///
+    /// As much of the `input` as fits in the `output` is written as one frame. If not
+    /// all input fits, the frame is a fragment and the message continues in the next
+    /// call. The message ends when `fin` is true and all input is used.
+    ///
+    /// `mask` must be set for [`Role::Client`] and must be a fresh random value for
+    /// each frame (RFC 6455 5.3). It must be `None` for [`Role::Server`].
+    ///
+    /// Returns `(input_used, output_used)`.
+    pub fn write(
+        &mut self,
+        kind: MessageKind,
+        input: &[u8],
+        fin: bool,
+        mask: Option<[u8; 4]>,
+        output: &mut [u8],
+    ) -> Result<(usize, usize), Error> {
+        self.check_mask(mask)?;
+
+        if self.closed {
+            return Err(Error::BadWebSocketFrame("frame after close".to_string()));
+        }
+
+        let opcode = match self.message {
+            None => kind.into(),
+            Some(k) if k == kind => Opcode::Continuation,
+            Some(_) => {
+                return Err(Error::BadWebSocketFrame(
+                    "message kind changed before fin".to_string(),
+                ))
+            }
+        };
+
+        let masked = mask.is_some();
+
+        // The header length depends on the payload length, which
+        // depends on how much space is left after the header.
+        let mut len = input.len().min(output.len());
+        while len > 0 && header_len(len, masked) + len > output.len() {
+            len = output
+                .len()
+                .saturating_sub(header_len(len, masked))
+                .min(len - 1);
+        }
+
+        if header_len(len, masked) > output.len() || (len == 0 && !input.is_empty()) {
+            return Err(Error::OutputOverflow);
+        }
+
+        let frame_fin = fin && len == input.len();
+
+        let output_used = write_frame(frame_fin, opcode, mask, &input[..len], output);
+
+        self.message = if frame_fin { None } else { Some(kind) };
+
+        Ok((len, output_used))
+    }
+
+    /// Write a ping frame.
+    ///
+    /// The payload can be at most 125 bytes. Returns the `output_used`.
+    pub fn write_ping(
+        &mut self,
+        payload: &[u8],
+        mask: Option<[u8; 4]>,
+        output: &mut [u8],
+    ) -> Result<usize, Error> {
+        self.write_control(Opcode::Ping, payload, mask, output)
+    }
+
+    /// Write a pong frame.
+    ///
+    /// The payload can be at most 125 bytes. Returns the `output_used`.
+    pub fn write_pong(
+        &mut self,
+        payload: &[u8],
+        mask: Option<[u8; 4]>,
+        output: &mut [u8],
+    ) -> Result<usize, Error> {
+        self.write_control(Opcode::Pong, payload, mask, output)
+    }
+
+    /// Write a close frame.
+    ///
+    /// A reason can only be sent together with a code, and can be at most 123 bytes.
+    /// No frames can be written after the close frame. Returns the `output_used`.
+    pub fn write_close(
+        &mut self,
+        code: Option<CloseCode>,
+        reason: &str,
+        mask: Option<[u8; 4]>,
+        output: &mut [u8],
+    ) -> Result<usize, Error> {
+        let mut payload = Vec::with_capacity(2 + reason.len());
+
+        if let Some(code) = code {
+            payload.extend_from_slice(&code.as_u16().to_be_bytes());
+            payload.extend_from_slice(reason.as_bytes());
+        } else if !reason.is_empty() {
+            return Err(Error::BadWebSocketFrame(
+                "close reason without code".to_string(),
+            ));
+        }
+
+        let output_used = self.write_control(Opcode::Close, &payload, mask, output)?;
+        self.closed = true;
+
+        Ok(output_used)
+    }
+
+    /// Tell if a data message has been started, but not ended.
+    pub fn is_in_message(&self) -> bool {
+        self.message.is_some()
+    }
+
+    /// Tell if a close frame has been written.
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    fn write_control(
+        &mut self,
+        opcode: Opcode,
+        payload: &[u8],
+        mask: Option<[u8; 4]>,
+        output: &mut [u8],
+    ) -> Result<usize, Error> {
+        self.check_mask(mask)?;
+
+        if self.closed {
+            return Err(Error::BadWebSocketFrame("frame after close".to_string()));
+        }
+
+        if payload.len() > MAX_CONTROL_PAYLOAD {
+            return Err(Error::BadWebSocketFrame(
+                "control frame payload too long".to_string(),
+            ));
+        }
+
+        if header_len(payload.len(), mask.is_some()) + payload.len() > output.len() {
+            return Err(Error::OutputOverflow);
+        }
+
+        Ok(write_frame(true, opcode, mask, payload, output))
+    }
+
+    fn check_mask(&self, mask: Option<[u8; 4]>) -> Result<(), Error> {
+        match (self.role, mask) {
+            (Role::Client, None) => Err(Error::BadWebSocketFrame(
+                "client frames must be masked".to_string(),
+            )),
+            (Role::Server, Some(_)) => Err(Error::BadWebSocketFrame(
+                "server frames must not be masked".to_string(),
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn header_len(payload_len: usize, masked: bool) -> usize {
+    let len = match payload_len {
+        0..=125 => 2,
+        126..=0xffff => 4,
+        _ => 10,
+    };
+    if masked {
+        len + 4
+    } else {
+        len
+    }
+}
+
+/// Write a frame. The caller must ensure the output is big enough.
+fn write_frame(
+    fin: bool,
+    opcode: Opcode,
+    mask: Option<[u8; 4]>,
+    payload: &[u8],
+    output: &mut [u8],
+) -> usize {
+    let fin_bit = if fin { 0x80 } else { 0 };
+    let mask_bit = if mask.is_some() { 0x80 } else { 0 };
+
+    output[0] = fin_bit | opcode.as_u8();
+
+    let mut pos = match payload.len() {
+        n @ 0..=125 => {
+            output[1] = mask_bit | n as u8;
+            2
+        }
+        n @ 126..=0xffff => {
+            output[1] = mask_bit | 126;
+            output[2..4].copy_from_slice(&(n as u16).to_be_bytes());
+            4
+        }
+        n => {
+            output[1] = mask_bit | 127;
+            output[2..10].copy_from_slice(&(n as u64).to_be_bytes());
+            10
+        }
+    };
+
+    if let Some(mask) = mask {
+        output[pos..pos + 4].copy_from_slice(&mask);
+        pos += 4;
+    }
+
+    let out = &mut output[pos..pos + payload.len()];
+    out.copy_from_slice(payload);
+    if let Some(mask) = mask {
+        apply_mask(mask, 0, out);
+    }
+
+    pos + payload.len()
+}
+
+fn apply_mask(mask: [u8; 4], offset: usize, data: &mut [u8]) {
+    for (i, b) in data.iter_mut().enumerate() {
+        *b ^= mask[(offset + i) % 4];
+    }
+}
+
+/// Decodes frames from an input buffer.
+///
+/// Payload of data frames is unmasked into the output buffer. Payload of control frames
+/// is collected internally and made available via [`FrameDecoder::take_control()`].
+#[derive(Debug)]
+pub struct FrameDecoder {
+    role: Role,
+    state: State,
+    message: Option<MessageKind>,
+    in_message: bool,
+    message_end: bool,
+    control: Vec<u8>,
+    received: Option<Control>,
+    closed: bool,
+}
+
+#[derive(Debug)]
+enum State {
+    Header,
+    Payload {
+        opcode: Opcode,
+        fin: bool,
+        mask: Option<[u8; 4]>,
+        remaining: u64,
+        offset: usize,
+    },
+}
+
+impl FrameDecoder {
+    /// Create a new decoder.
+    pub fn new(role: Role) -> Self {
+        FrameDecoder {
+            role,
+            state: State::Header,
+            message: None,
+            in_message: false,
+            message_end: false,
+            control: Vec::new(),
+            received: None,
+            closed: false,
+        }
+    }
+
+    /// Read frames from the input.
+    ///
+    /// Data payload is written to the output. Reading stops at the end of each frame,
+    /// which means the caller should check [`FrameDecoder::is_message_end()`] and
+    /// [`FrameDecoder::take_control()`] after each call.
+    ///
+    /// Returns `(input_used, output_used)`.
+    pub fn read(&mut self, input: &[u8], output: &mut [u8]) -> Result<(usize, usize), Error> {
+        let mut input_used = 0;
+
+        // Only ever true for the read that ended the message.
+        self.message_end = false;
+
+        if matches!(self.state, State::Header) {
+            if self.closed {
+                return Err(Error::BadWebSocketFrame("frame after close".to_string()));
+            }
+
+            let Some(header_used) = self.read_header(input)? else {
+                return Ok((0, 0));
+            };
+            input_used = header_used;
+        }
+
+        let State::Payload {
+            opcode,
+            fin,
+            mask,
+            remaining,
+            offset,
+        } = &mut self.state
+        else {
+            unreachable!("read_header sets State::Payload");
+        };
+
+        let input = &input[input_used..];
+        let max = (*remaining).min(usize::MAX as u64) as usize;
+
+        let output_used = if opcode.is_control() {
+            let amount = max.min(input.len());
+            let start = self.control.len();
+            self.control.extend_from_slice(&input[..amount]);
+            if let Some(mask) = mask {
+                apply_mask(*mask, *offset, &mut self.control[start..]);
+            }
+            input_used += amount;
+            *offset += amount;
+            *remaining -= amount as u64;
+            0
+        } else {
+            let amount = max.min(input.len()).min(output.len());
+            output[..amount].copy_from_slice(&input[..amount]);
+            if let Some(mask) = mask {
+                apply_mask(*mask, *offset, &mut output[..amount]);
+            }
+            input_used += amount;
+            *offset += amount;
+            *remaining -= amount as u64;
+            amount
+        };
+
+        if *remaining == 0 {
+            let opcode = *opcode;
+            let fin = *fin;
+            self.state = State::Header;
+            self.end_frame(opcode, fin)?;
+        }
+
+        Ok((input_used, output_used))
+    }
+
+    /// Kind of the data message currently, or most recently, read.
+    pub fn message_kind(&self) -> Option<MessageKind> {
+        self.message
+    }
+
+    /// Tell if the last read completed the final frame of a data message.
+    pub fn is_message_end(&self) -> bool {
+        self.message_end
+    }
+
+    /// Take a control frame that was completely read.
+    pub fn take_control(&mut self) -> Option<Control> {
+        self.received.take()
+    }
+
+    /// Tell if a close frame has been read.
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    fn read_header(&mut self, input: &[u8]) -> Result<Option<usize>, Error> {
+        if input.len() < 2 {
+            return Ok(None);
+        }
+
+        let fin = input[0] & 0x80 > 0;
+
+        if input[0] & 0x70 > 0 {
+            return Err(Error::BadWebSocketFrame(
+                "reserved bits set without extension".to_string(),
+            ));
+        }
+
+        let opcode = Opcode::from_u8(input[0] & 0x0f).ok_or_else(|| {
+            Error::BadWebSocketFrame(format!("unknown opcode: {}", input[0] & 0x0f))
+        })?;
+
+        let masked = input[1] & 0x80 > 0;
+
+        match (self.role, masked) {
+            (Role::Client, true) => {
+                return Err(Error::BadWebSocketFrame(
+                    "server frames must not be masked".to_string(),
+                ))
+            }
+            (Role::Server, false) => {
+                return Err(Error::BadWebSocketFrame(
+                    "client frames must be masked".to_string(),
+                ))
+            }
+            _ => {}
+        }
+
+        let (len_size, len) = match input[1] & 0x7f {
+            126 => (2, None),
+            127 => (8, None),
+            n => (0, Some(n as u64)),
+        };
+
+        let header_len = 2 + len_size + if masked { 4 } else { 0 };
+        debug_assert!(header_len <= MAX_HEADER_LEN);
+
+        if input.len() < header_len {
+            return Ok(None);
+        }
+
+        let len = match len {
+            Some(v) => v,
+            None => {
+                let mut buf = [0; 8];
+                buf[8 - len_size..].copy_from_slice(&input[2..2 + len_size]);
+                let v = u64::from_be_bytes(buf);
+                if v >> 63 > 0 {
+                    return Err(Error::BadWebSocketFrame(
+                        "most significant bit of length set".to_string(),
+                    ));
+                }
+                v
+            }
+        };
+
+        let mask = if masked {
+            let p = 2 + len_size;
+            Some([input[p], input[p + 1], input[p + 2], input[p + 3]])
+        } else {
+            None
+        };
+
+        if opcode.is_control() {
+            if !fin {
+                return Err(Error::BadWebSocketFrame(
+                    "fragmented control frame".to_string(),
+                ));
+            }
+            if len > MAX_CONTROL_PAYLOAD as u64 {
+                return Err(Error::BadWebSocketFrame(
+                    "control frame payload too long".to_string(),
+                ));
+            }
+            self.control.clear();
+        } else {
+            match (opcode, self.in_message) {
+                (Opcode::Continuation, false) => {
+                    return Err(Error::BadWebSocketFrame(
+                        "continuation without message".to_string(),
+                    ))
+                }
+                (Opcode::Text, true) | (Opcode::Binary, true) => {
+                    return Err(Error::BadWebSocketFrame(
+                        "new message before fin".to_string(),
+                    ))
+                }
+                (Opcode::Text, false) => self.message = Some(MessageKind::Text),
+                (Opcode::Binary, false) => self.message = Some(MessageKind::Binary),
+                _ => {}
+            }
+            self.in_message = true;
+        }
+
+        self.state = State::Payload {
+            opcode,
+            fin,
+            mask,
+            remaining: len,
+            offset: 0,
+        };
+
+        Ok(Some(header_len))
+    }
+
+    fn end_frame(&mut self, opcode: Opcode, fin: bool) -> Result<(), Error> {
+        let control = match opcode {
+            Opcode::Ping => Control::Ping(self.control.clone()),
+            Opcode::Pong => Control::Pong(self.control.clone()),
+            Opcode::Close => {
+                self.closed = true;
+                parse_close(&self.control)?
+            }
+            _ => {
+                if fin {
+                    self.in_message = false;
+                    self.message_end = true;
+                }
+                return Ok(());
+            }
+        };
+
+        self.received = Some(control);
+
+        Ok(())
+    }
+}
+
+fn parse_close(payload: &[u8]) -> Result<Control, Error> {
+    if payload.is_empty() {
+        return Ok(Control::Close {
+            code: None,
+            reason: String::new(),
+        });
+    }
+
+    if payload.len() == 1 {
+        return Err(Error::BadWebSocketFrame(
+            "close payload of one byte".to_string(),
+        ));
+    }
+
+    let code = u16::from_be_bytes([payload[0], payload[1]]);
+    let code = CloseCode::from_u16(code)
+        .ok_or_else(|| Error::BadWebSocketFrame(format!("invalid close code: {}", code)))?;
+
+    let reason = std::str::from_utf8(&payload[2..])
+        .map_err(|_| Error::BadWebSocketFrame("close reason is not utf-8".to_string()))?;
+
+    Ok(Control::Close {
+        code: Some(code),
+        reason: reason.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const MASK: [u8; 4] = [0x37, 0xfa, 0x21, 0x3d];
+
+    #[test]
+    fn test_encode_unmasked() -> Result<(), Error> {
+        // RFC 6455 5.7
+        let mut e = FrameEncoder::new(Role::Server);
+        let mut b = [0; 1024];
+        let (i, o) = e.write(MessageKind::Text, b"Hello", true, None, &mut b)?;
+        assert_eq!((i, o), (5, 7));
+        assert_eq!(&b[..o], b"\x81\x05Hello");
+        Ok(())
+    }
+
+    #[test]
+    fn test_encode_masked() -> Result<(), Error> {
+        // RFC 6455 5.7
+        let mut e = FrameEncoder::new(Role::Client);
+        let mut b = [0; 1024];
+        let (_, o) = e.write(MessageKind::Text, b"Hello", true, Some(MASK), &mut b)?;
+        assert_eq!(
+            &b[..o],
+            &[0x81, 0x85, 0x37, 0xfa, 0x21, 0x3d, 0x7f, 0x9f, 0x4d, 0x51, 0x58]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_encode_mask_role() {
+        let mut b = [0; 1024];
+        let mut e = FrameEncoder::new(Role::Client);
+        assert!(e
+            .write(MessageKind::Text, b"a", true, None, &mut b)
+            .is_err());
+        let mut e = FrameEncoder::new(Role::Server);
+        assert!(e.write_ping(b"", Some(MASK), &mut b).is_err());
+    }
+
+    #[test]
+    fn test_encode_fragmented() -> Result<(), Error> {
+        let mut e = FrameEncoder::new(Role::Server);
+        let mut b = [0; 5];
+
+        // Only 3 bytes of payload fit after the 2 byte header.
+        let (i, o) = e.write(MessageKind::Text, b"Hello", true, None, &mut b)?;
+        assert_eq!((i, o), (3, 5));
+        assert_eq!(&b[..o], b"\x01\x03Hel");
+        assert!(e.is_in_message());
+
+        let (i, o) = e.write(MessageKind::Text, b"lo", true, None, &mut b)?;
+        assert_eq!((i, o), (2, 4));
+        assert_eq!(&b[..o], b"\x80\x02lo");
+        assert!(!e.is_in_message());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_encode_lengths() -> Result<(), Error> {
+        let mut e = FrameEncoder::new(Role::Server);
+        let mut b = vec![0; 70_000];
+
+        let (_, o) = e.write(MessageKind::Binary, &[0; 256], true, None, &mut b)?;
+        assert_eq!(o, 260);
+        assert_eq!(&b[..4], &[0x82, 0x7e, 0x01, 0x00]);
+
+        let (_, o) = e.write(MessageKind::Binary, &[0; 65536], true, None, &mut b)?;
+        assert_eq!(o, 65546);
+        assert_eq!(&b[..10], &[0x82, 0x7f, 0, 0, 0, 0, 0, 1, 0, 0]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_encode_control() -> Result<(), Error> {
+        let mut e = FrameEncoder::new(Role::Server);
+        let mut b = [0; 1024];
+
+        let o = e.write_ping(b"Hello", None, &mut b)?;
+        assert_eq!(&b[..o], b"\x89\x05Hello");
+
+        let o = e.write_pong(b"Hello", None, &mut b)?;
+        assert_eq!(&b[..o], b"\x8a\x05Hello");
+
+        assert!(e.write_ping(&[0; 126], None, &mut b).is_err());
+        assert_eq!(
+            e.write_ping(b"", None, &mut b[..1]),
+            Err(Error::OutputOverflow)
+        );
+
+        let o = e.write_close(Some(CloseCode::NORMAL), "bye", None, &mut b)?;
+        assert_eq!(&b[..o], b"\x88\x05\x03\xe8bye");
+        assert!(e.is_closed());
+
+        assert!(e.write_ping(b"", None, &mut b).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_unmasked() -> Result<(), Error> {
+        let mut d = FrameDecoder::new(Role::Client);
+        let mut b = [0; 1024];
+        assert_eq!(d.read(b"\x81", &mut b)?, (0, 0));
+        assert_eq!(d.read(b"\x81\x05Hello", &mut b)?, (7, 5));
+        assert_eq!(&b[..5], b"Hello");
+        assert_eq!(d.message_kind(), Some(MessageKind::Text));
+        assert!(d.is_message_end());
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_masked() -> Result<(), Error> {
+        let mut d = FrameDecoder::new(Role::Server);
+        let mut b = [0; 1024];
+        let input = [
+            0x81, 0x85, 0x37, 0xfa, 0x21, 0x3d, 0x7f, 0x9f, 0x4d, 0x51, 0x58,
+        ];
+        // Partial payload keeps the mask offset.
+        assert_eq!(d.read(&input[..8], &mut b)?, (8, 2));
+        assert_eq!(&b[..2], b"He");
+        assert!(!d.is_message_end());
+        assert_eq!(d.read(&input[8..], &mut b)?, (3, 3));
+        assert_eq!(&b[..3], b"llo");
+        assert!(d.is_message_end());
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_mask_role() {
+        let mut b = [0; 1024];
+        let mut d = FrameDecoder::new(Role::Server);
+        assert!(d.read(b"\x81\x05Hello", &mut b).is_err());
+    }
+
+    #[test]
+    fn test_decode_fragmented_with_ping() -> Result<(), Error> {
+        let mut d = FrameDecoder::new(Role::Client);
+        let mut b = [0; 1024];
+        let input = b"\x01\x03Hel\x89\x02hi\x80\x02lo";
+
+        assert_eq!(d.read(input, &mut b)?, (5, 3));
+        assert!(!d.is_message_end());
+        assert_eq!(d.take_control(), None);
+
+        assert_eq!(d.read(&input[5..], &mut b)?, (4, 0));
+        assert_eq!(d.take_control(), Some(Control::Ping(b"hi".to_vec())));
+        assert!(!d.is_message_end());
+
+        assert_eq!(d.read(&input[9..], &mut b)?, (4, 2));
+        assert_eq!(&b[..2], b"lo");
+        assert!(d.is_message_end());
+        assert_eq!(d.message_kind(), Some(MessageKind::Text));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_message_end_once() -> Result<(), Error> {
+        let mut d = FrameDecoder::new(Role::Client);
+        let mut b = [0; 1024];
+
+        assert_eq!(d.read(b"\x81\x02hi", &mut b)?, (4, 2));
+        assert!(d.is_message_end());
+
+        assert_eq!(d.read(b"\x89\x00", &mut b)?, (2, 0));
+        assert_eq!(d.take_control(), Some(Control::Ping(vec![])));
+        assert!(!d.is_message_end());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_bad_sequence() {
+        let mut b = [0; 1024];
+
+        let mut d = FrameDecoder::new(Role::Client);
+        assert!(d.read(b"\x80\x02lo", &mut b).is_err());
+
+        let mut d = FrameDecoder::new(Role::Client);
+        d.read(b"\x01\x03Hel", &mut b).unwrap();
+        assert!(d.read(b"\x82\x00", &mut b).is_err());
+
+        let mut d = FrameDecoder::new(Role::Client);
+        assert!(d.read(b"\x09\x00", &mut b).is_err());
+
+        let mut d = FrameDecoder::new(Role::Client);
+        assert!(d.read(b"\xc1\x00", &mut b).is_err());
+
+        let mut d = FrameDecoder::new(Role::Client);
+        assert!(d.read(b"\x83\x00", &mut b).is_err());
+    }
+
+    #[test]
+    fn test_decode_lengths() -> Result<(), Error> {
+        let mut d = FrameDecoder::new(Role::Client);
+        let mut b = vec![0; 70_000];
+
+        let mut input = vec![0x82, 0x7e, 0x01, 0x00];
+        input.extend_from_slice(&[1; 256]);
+        assert_eq!(d.read(&input, &mut b)?, (260, 256));
+        assert!(d.is_message_end());
+
+        let mut input = vec![0x82, 0x7f, 0, 0, 0, 0, 0, 1, 0, 0];
+        input.extend_from_slice(&[1; 65536]);
+        assert_eq!(d.read(&input, &mut b)?, (65546, 65536));
+        assert!(d.is_message_end());
+
+        let mut d = FrameDecoder::new(Role::Client);
+        assert!(d
+            .read(&[0x82, 0x7f, 0x80, 0, 0, 0, 0, 0, 0, 0], &mut b)
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_close() -> Result<(), Error> {
+        let mut b = [0; 1024];
+
+        let mut d = FrameDecoder::new(Role::Client);
+        assert_eq!(d.read(b"\x88\x05\x03\xe8bye", &mut b)?, (7, 0));
+        assert_eq!(
+            d.take_control(),
+            Some(Control::Close {
+                code: Some(CloseCode::NORMAL),
+                reason: "bye".to_string()
+            })
+        );
+        assert!(d.is_closed());
+        assert!(d.read(b"\x81\x00", &mut b).is_err());
+
+        let mut d = FrameDecoder::new(Role::Client);
+        d.read(b"\x88\x00", &mut b)?;
+        assert_eq!(
+            d.take_control(),
+            Some(Control::Close {
+                code: None,
+                reason: String::new()
+            })
+        );
+
+        let mut d = FrameDecoder::new(Role::Client);
+        assert!(d.read(b"\x88\x02\x03\xed", &mut b).is_err());
+
+        let mut d = FrameDecoder::new(Role::Client);
+        assert!(d.read(b"\x88\x01\x03", &mut b).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_roundtrip() -> Result<(), Error> {
+        let mut e = FrameEncoder::new(Role::Client);
+        let mut d = FrameDecoder::new(Role::Server);
+        let mut wire = [0; 1024];
+        let mut out = [0; 1024];
+
+        let (_, o) = e.write(
+            MessageKind::Binary,
+            &[1, 2, 3, 4, 5],
+            true,
+            Some(MASK),
+            &mut wire,
+        )?;
+        assert_eq!(d.read(&wire[..o], &mut out)?, (o, 5));
+        assert_eq!(&out[..5], &[1, 2, 3, 4, 5]);
+        assert_eq!(d.message_kind(), Some(MessageKind::Binary));
+
+        Ok(())
+    }
+}
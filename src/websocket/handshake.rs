@@ -0,0 +1,250 @@
+use http::{Method, Response, StatusCode, Version};
+
+use crate::client::flow::state::Prepare;
+use crate::client::flow::Flow;
+use crate::ext::HeaderIterExt;
+use crate::util::base64;
+use crate::Error;
+
+/// GUID appended to the key before hashing (RFC 6455 1.3).
+const ACCEPT_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// The only protocol version in RFC 6455.
+const WEBSOCKET_VERSION: &str = "13";
+
+/// The client side of the WebSocket opening handshake.
+///
+/// The handshake is an ordinary HTTP/1.1 `GET` with upgrade headers. This type adds
+/// those headers to a [`Flow<Prepare>`](Flow) and verifies the `101 Switching Protocols`
+/// response received in `RecvResponse`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Handshake {
+    key: String,
+    accept: String,
+}
+
+impl Handshake {
+    /// Create a handshake from a 16 byte nonce.
+    ///
+    /// The nonce must be randomly selected for each connection (RFC 6455 4.1). Since
+    /// ureq-proto doesn't depend on a random number generator, the caller provides it.
+    pub fn new(nonce: [u8; 16]) -> Self {
+        let key = base64(&nonce);
+        let accept = accept_for_key(&key);
+        Handshake { key, accept }
+    }
+
+    /// The `sec-websocket-key` header value sent to the server.
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// The `sec-websocket-accept` header value expected from the server.
+    pub fn accept(&self) -> &str {
+        &self.accept
+    }
+
+    /// Add the opening handshake headers to the flow.
+    ///
+    /// The request must be a `GET` using HTTP/1.1.
+    pub fn prepare<B>(&self, flow: &mut Flow<B, Prepare>) -> Result<(), Error> {
+        if flow.method() != Method::GET {
+            return Err(Error::BadWebSocketHandshake(format!(
+                "method must be GET: {}",
+                flow.method()
+            )));
+        }
+
+        if flow.version() != Version::HTTP_11 {
+            return Err(Error::BadWebSocketHandshake(
+                "version must be HTTP/1.1".to_string(),
+            ));
+        }
+
+        flow.header("upgrade", "websocket")?;
+        flow.header("connection", "Upgrade")?;
+        flow.header("sec-websocket-key", self.key.as_str())?;
+        flow.header("sec-websocket-version", WEBSOCKET_VERSION)?;
+
+        Ok(())
+    }
+
+    /// Verify the server response to the opening handshake.
+    ///
+    /// The `connection` and `upgrade` headers are already checked by the flow when
+    /// receiving a 101 response. This checks the status and `sec-websocket-accept`.
+    pub fn verify(&self, response: &Response<()>) -> Result<(), Error> {
+        if response.status() != StatusCode::SWITCHING_PROTOCOLS {
+            return Err(Error::BadWebSocketHandshake(format!(
+                "unexpected status: {}",
+                response.status()
+            )));
+        }
+
+        if !response.headers().iter().has_token("upgrade", "websocket") {
+            return Err(Error::BadWebSocketHandshake(
+                "upgrade is not websocket".to_string(),
+            ));
+        }
+
+        let accept = response
+            .headers()
+            .get("sec-websocket-accept")
+            .ok_or_else(|| {
+                Error::BadWebSocketHandshake("missing sec-websocket-accept".to_string())
+            })?;
+
+        if accept.as_bytes() != self.accept.as_bytes() {
+            return Err(Error::BadWebSocketHandshake(
+                "sec-websocket-accept mismatch".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+fn accept_for_key(key: &str) -> String {
+    let mut input = Vec::with_capacity(key.len() + ACCEPT_GUID.len());
+    input.extend_from_slice(key.as_bytes());
+    input.extend_from_slice(ACCEPT_GUID.as_bytes());
+    base64(&sha1(&input))
+}
+
+/// SHA-1 (RFC 3174). Only used for the handshake, where it is mandated by RFC 6455.
+fn sha1(input: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+
+    let bit_len = (input.len() as u64).wrapping_mul(8);
+
+    let mut message = input.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&bit_len.to_be_bytes());
+
+    for block in message.chunks(64) {
+        let mut w = [0_u32; 80];
+
+        for (i, word) in block.chunks(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+
+        for (i, wi) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5A827999),
+                20..=39 => (b ^ c ^ d, 0x6ED9EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6),
+            };
+
+            let temp = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(*wi);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+
+        h[0] = h[0].wrapping_add(a);
+        h[1] = h[1].wrapping_add(b);
+        h[2] = h[2].wrapping_add(c);
+        h[3] = h[3].wrapping_add(d);
+        h[4] = h[4].wrapping_add(e);
+    }
+
+    let mut out = [0; 20];
+    for (i, v) in h.iter().enumerate() {
+        out[i * 4..i * 4 + 4].copy_from_slice(&v.to_be_bytes());
+    }
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use http::Request;
+
+    use crate::ParsePolicy;
+
+    fn hex(v: &[u8]) -> String {
+        v.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    #[test]
+    fn test_sha1() {
+        assert_eq!(hex(&sha1(b"")), "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+        assert_eq!(
+            hex(&sha1(b"abc")),
+            "a9993e364706816aba3e25717850c26c9cd0d89d"
+        );
+        assert_eq!(
+            hex(&sha1(
+                b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"
+            )),
+            "84983e441c3bd26ebaae4aa1f95129e5e54670f1"
+        );
+    }
+
+    #[test]
+    fn test_rfc_example() {
+        // RFC 6455 1.3
+        let h = Handshake::new(*b"the sample nonce");
+        assert_eq!(h.key(), "dGhlIHNhbXBsZSBub25jZQ==");
+        assert_eq!(h.accept(), "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+    }
+
+    #[test]
+    fn test_prepare_requires_get() {
+        let h = Handshake::new([0; 16]);
+        let req = Request::post("http://q.test/chat").body(()).unwrap();
+        let mut flow = Flow::new(req, ParsePolicy::default()).unwrap();
+        assert!(matches!(
+            h.prepare(&mut flow),
+            Err(Error::BadWebSocketHandshake(_))
+        ));
+    }
+
+    #[test]
+    fn test_verify() {
+        let h = Handshake::new(*b"the sample nonce");
+
+        let ok = Response::builder()
+            .status(101)
+            .header("upgrade", "websocket")
+            .header("connection", "upgrade")
+            .header("sec-websocket-accept", "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=")
+            .body(())
+            .unwrap();
+        assert_eq!(h.verify(&ok), Ok(()));
+
+        let wrong = Response::builder()
+            .status(101)
+            .header("upgrade", "websocket")
+            .header("connection", "upgrade")
+            .header("sec-websocket-accept", "dGhlIHNhbXBsZSBub25jZQ==")
+            .body(())
+            .unwrap();
+        assert_eq!(
+            h.verify(&wrong),
+            Err(Error::BadWebSocketHandshake(
+                "sec-websocket-accept mismatch".to_string()
+            ))
+        );
+
+        let status = Response::builder().status(200).body(()).unwrap();
+        assert!(h.verify(&status).is_err());
+    }
+}
@@ -0,0 +1,115 @@
+//! WebSocket protocol (RFC 6455)
+//!
+//! Sans-IO like the rest of the crate. The opening handshake runs on top of the
+//! [client flow](crate::client), and the frames are read and written via buffers
+//! using the same `(input_used, output_used)` conventions as
+//! [`Flow<RecvBody>::read()`](crate::client::flow::Flow).
+//!
+//! * [`Handshake`] adds the upgrade headers in the `Prepare` state and verifies
+//!   the `101 Switching Protocols` response.
+//! * [`FrameEncoder`] writes data messages, potentially fragmented, as well as ping,
+//!   pong and close frames.
+//! * [`FrameDecoder`] reads frames, unmasking data into the output buffer and
+//!   collecting control frames.
+//!
+//! The nonce for the handshake and the masks for client frames must be random.
+//! This crate has no random number generator, so those are provided by the caller.
+//!
+//! # Example
+//!
+//! ```
+//! use ureq_proto::client::flow::*;
+//! use ureq_proto::http::Request;
+//! use ureq_proto::websocket::*;
+//! use ureq_proto::ParsePolicy;
+//!
+//! let request = Request::get("http://example.test/chat").body(()).unwrap();
+//!
+//! // ********************************** Prepare
+//!
+//! let mut flow = Flow::new(request, ParsePolicy::default()).unwrap();
+//!
+//! // Should be 16 random bytes.
+//! let handshake = Handshake::new(*b"the sample nonce");
+//! handshake.prepare(&mut flow).unwrap();
+//!
+//! // ********************************** SendRequest
+//!
+//! let mut flow = flow.proceed();
+//!
+//! let mut output = vec![0_u8; 1024];
+//! let output_used = flow.write(&mut output).unwrap();
+//!
+//! assert_eq!(&output[..output_used], b"\
+//!     GET /chat HTTP/1.1\r\n\
+//!     upgrade: websocket\r\n\
+//!     connection: Upgrade\r\n\
+//!     sec-websocket-key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+//!     sec-websocket-version: 13\r\n\
+//!     host: example.test\r\n\
+//!     \r\n");
+//!
+//! // ********************************** RecvResponse
+//!
+//! let mut flow = match flow.proceed() {
+//!     Ok(Some(SendRequestResult::RecvResponse(v))) => v,
+//!     _ => panic!(),
+//! };
+//!
+//! // The server replies, and immediately sends a first frame.
+//! let input = b"HTTP/1.1 101 Switching Protocols\r\n\
+//!     upgrade: websocket\r\n\
+//!     connection: Upgrade\r\n\
+//!     sec-websocket-accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n\
+//!     \r\n\
+//!     \x81\x05Hello";
+//!
+//! let (_, response) = flow.try_response(input).unwrap();
+//! handshake.verify(&response.unwrap()).unwrap();
+//!
+//! // ********************************** Upgrade
+//!
+//! let flow = match flow.proceed() {
+//!     Some(RecvResponseResult::Upgrade(v)) => v,
+//!     _ => panic!(),
+//! };
+//!
+//! // Bytes after the response belong to the WebSocket.
+//! let leftover = flow.into_leftover();
+//!
+//! let mut decoder = FrameDecoder::new(Role::Client);
+//! let (_, output_used) = decoder.read(&leftover, &mut output).unwrap();
+//!
+//! assert_eq!(&output[..output_used], b"Hello");
+//! assert_eq!(decoder.message_kind(), Some(MessageKind::Text));
+//! assert!(decoder.is_message_end());
+//!
+//! // Client frames are masked. The mask should be 4 random bytes.
+//! let mut encoder = FrameEncoder::new(Role::Client);
+//! let mask = Some([0x37, 0xfa, 0x21, 0x3d]);
+//!
+//! let (_, output_used) = encoder
+//!     .write(MessageKind::Text, b"Hello", true, mask, &mut output)
+//!     .unwrap();
+//!
+//! assert_eq!(&output[..output_used], b"\
+//!     \x81\x85\x37\xfa\x21\x3d\x7f\x9f\x4d\x51\x58");
+//!
+//! let output_used = encoder
+//!     .write_close(Some(CloseCode::NORMAL), "", mask, &mut output)
+//!     .unwrap();
+//! assert_eq!(output_used, 8);
+//! ```
+//!
+//! # Out of scope:
+//!
+//! * Extensions, such as permessage-deflate
+//! * Subprotocol negotiation (can be done with regular headers)
+//! * UTF-8 validation of text messages
+//!
+
+mod frame;
+mod handshake;
+
+pub use frame::{CloseCode, Control, FrameDecoder, FrameEncoder, MessageKind, Role};
+pub use handshake::Handshake;